@@ -1,16 +1,38 @@
 use std::path::PathBuf;
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
 use chrono::NaiveDate;
 use clap_complete::Shell;
+use glob::Pattern;
 use crate::module::import::OrderTimeType;
+use crate::module::server_log::LogLevel;
+use crate::utils::output::OutputFormat;
+use crate::command::export::ExportFormat;
+use crate::command::apply::prune::PruneKind;
+use crate::command::apply::merge::MergeStrategy;
 
 #[derive(Parser)]
-#[command(bin_name = "hedge", name = "hedge", version, about = "Hedge Command Line Application")]
-pub enum Cli {
+#[command(bin_name = "hedge", name = "hedge", version, about = "Hedge Command Line Application", after_help = "Exit codes: 0 success, 1 failed, 2 input error, 3 connection error, 4 partial failure.")]
+pub struct Cli {
+    #[arg(long, global = true, default_value = "text", help = "output format")]
+    pub output: OutputFormat,
+    #[arg(short, long, global = true, action = ArgAction::Count, help = "print verbose output, -vv to trace requests as --trace")]
+    pub verbose: u8,
+    #[arg(long, global = true, help = "trace server and download requests to stderr (HEDGE_CLI_LOG=file writes them into the log directory instead)")]
+    pub trace: bool,
+    #[arg(long, global = true, help = "use this channel for this invocation only, overriding HEDGE_CHANNEL and the using channel")]
+    pub channel: Option<String>,
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
     #[command(about = "Start hedge application")]
     App,
     #[command(about = "Apply anywhere files for data updates")]
     Apply(Apply),
+    #[command(about = "Export metadata and settings into an apply file")]
+    Export(Export),
     #[command(subcommand, about = "Cli Channel control")]
     Channel(Channel),
     #[command(subcommand, about = "Background service process control")]
@@ -26,26 +48,124 @@ pub enum Cli {
 }
 
 #[derive(Args)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct Apply {
-    #[arg(short, long, help = "read from files in directory")]
+    #[command(subcommand)]
+    pub command: Option<ApplyCommand>,
+    #[command(flatten)]
+    pub source: ApplySource,
+    #[arg(long, help = "print changes compared with server without applying")]
+    pub dry_run: bool,
+    #[arg(long, value_delimiter = ',', help = "delete entities of these kinds which are not declared in files")]
+    pub prune: Vec<PruneKind>,
+    #[arg(short, long, help = "skip confirmation of prune")]
+    pub yes: bool,
+    #[arg(long, default_value_t = 500, help = "max count of items submitted in one bulk request")]
+    pub chunk_size: usize,
+    #[arg(long, default_value_t = 3, help = "retry times of a failed bulk request")]
+    pub retry: u32,
+    #[arg(long, help = "ignore checkpoint of previous apply and start from the beginning")]
+    pub no_resume: bool,
+    #[arg(short, long, action = ArgAction::Count, help = "print verbose output, same as the global -v")]
+    pub verbose: u8,
+}
+
+#[derive(Subcommand)]
+pub enum ApplyCommand {
+    #[command(about = "Check apply files and report errors with locations")]
+    Validate {
+        #[command(flatten)]
+        source: ApplySource,
+        #[arg(long, help = "print JSON Schema of apply files instead")]
+        schema: bool
+    }
+}
+
+#[derive(Args)]
+pub struct ApplySource {
+    #[arg(short, long, help = "read from files in directory recursively")]
     pub directory: Option<Vec<PathBuf>>,
+    #[arg(short, long, value_parser = Pattern::new, help = "only read files in directory matching these glob patterns")]
+    pub glob: Vec<Pattern>,
     #[arg(short, long, help = "read from file")]
     pub file: Option<Vec<PathBuf>>,
     #[arg(short, long, help = "read from stdin")]
     pub input: bool,
-    #[arg(short, long, help = "print verbose output")]
-    pub verbose: bool,
+    #[arg(long = "var", value_name = "NAME=VALUE", help = "define variable used in files that declare a vars section")]
+    pub vars: Vec<String>,
+    #[arg(long, default_value = "fail", help = "how to handle entities declared more than once")]
+    pub merge: MergeStrategy,
+}
+
+#[derive(Args)]
+pub struct Export {
+    #[arg(short, long, help = "write to file instead of stdout")]
+    pub file: Option<PathBuf>,
+    #[arg(long, help = "output format, inferred from file extension by default")]
+    pub format: Option<ExportFormat>,
+    #[arg(long, help = "export tags")]
+    pub tags: bool,
+    #[arg(long, help = "export topics")]
+    pub topics: bool,
+    #[arg(long, help = "export authors")]
+    pub authors: bool,
+    #[arg(long, help = "export setting")]
+    pub setting: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Channel {
-    #[command(about = "Show using and all channels")]
+    #[command(about = "Show using and all channels with their server status and disk usage")]
     Info,
+    #[command(about = "Show details of a channel")]
+    Show {
+        #[arg(help = "channel name")]
+        channel_name: String
+    },
     #[command(about = "Change using channel")]
     Use {
         #[arg(help = "target channel name")]
         channel_name: String
-    }
+    },
+    #[command(about = "Create a new channel")]
+    Create {
+        #[arg(help = "channel name")]
+        channel_name: String
+    },
+    #[command(about = "Remove a channel and all its data")]
+    Remove {
+        #[arg(help = "channel name")]
+        channel_name: String,
+        #[arg(short, long, help = "skip confirmation")]
+        yes: bool
+    },
+    #[command(about = "Rename a channel")]
+    Rename {
+        #[arg(help = "channel name")]
+        channel_name: String,
+        #[arg(help = "new channel name")]
+        new_name: String
+    },
+    #[command(about = "Copy a channel with all its data")]
+    Copy {
+        #[arg(help = "channel name")]
+        channel_name: String,
+        #[arg(help = "new channel name")]
+        new_name: String
+    },
+    #[command(about = "Log in to a remote server with using channel")]
+    Login {
+        #[arg(long, help = "remote server host, as host:port or full URL with scheme and base path")]
+        host: String,
+        #[arg(long, help = "access token, read from stdin if omitted")]
+        token: Option<String>,
+        #[arg(long, help = "store token in cli credentials file readable only by current user, instead of client.dat")]
+        credentials_file: bool
+    },
+    #[command(about = "Remove stored token of using channel")]
+    Logout,
+    #[command(about = "Show login status of using channel")]
+    Whoami
 }
 
 #[derive(Subcommand)]
@@ -53,13 +173,59 @@ pub enum Server {
     #[command(about = "Show service status")]
     Status,
     #[command(about = "Start service and keep it running")]
-    Start,
+    Start {
+        #[arg(long, help = "wait for a service launched by others (e.g. systemd) instead of launching it")]
+        no_spawn: bool
+    },
     #[command(about = "Stop service")]
-    Stop,
+    Stop {
+        #[arg(long, help = "shut down the service now instead of only exiting permanent mode")]
+        now: bool,
+        #[arg(long, default_value_t = 30, help = "seconds to wait for a graceful shutdown before killing the service")]
+        timeout: u64
+    },
+    #[command(about = "Run in foreground, keep service running and restart it when it exits, also usable as a systemd service")]
+    Supervise {
+        #[arg(long, default_value_t = 10, help = "seconds between health checks")]
+        interval: u64,
+        #[arg(long, default_value_t = 300, help = "max seconds to wait before restarting a crashed service")]
+        max_backoff: u64,
+        #[arg(long, default_value_t = 120, help = "seconds an alive but unhealthy service is tolerated before it is restarted")]
+        unhealthy_timeout: u64
+    },
+    #[command(about = "Generate systemd user units for service, and optionally install them")]
+    InstallService {
+        #[arg(long, help = "write units into the systemd user unit directory and reload systemd")]
+        install: bool,
+        #[arg(long, help = "also enable and start units, implies --install")]
+        enable: bool,
+        #[arg(long, help = "hedge command run by a maintenance timer, e.g. \"source-data download\"")]
+        timer_command: Option<String>,
+        #[arg(long, default_value = "daily", help = "OnCalendar expression of the maintenance timer")]
+        timer_calendar: String
+    },
+    #[command(about = "Rotate server.log as starting service does, used by generated systemd units")]
+    RotateLog,
+    #[command(about = "Shut down service gracefully and start it again")]
+    Restart {
+        #[arg(long, default_value_t = 30, help = "seconds to wait for a graceful shutdown before killing the service")]
+        timeout: u64
+    },
     #[command(about = "Force kill service")]
     Kill,
     #[command(about = "Print server.log")]
-    Log
+    Log {
+        #[arg(short, long, help = "keep printing new lines, like tail -f")]
+        follow: bool,
+        #[arg(short = 'n', long, help = "print only the last N lines")]
+        lines: Option<usize>,
+        #[arg(long, help = "print only lines after this time, as \"2024-01-01 12:00:00\", \"2024-01-01\" or relative \"30m\", \"2h\", \"1d\"")]
+        since: Option<String>,
+        #[arg(long, help = "print only lines containing this text")]
+        grep: Option<String>,
+        #[arg(long, help = "print only lines of this level or above")]
+        level: Option<LogLevel>
+    }
 }
 
 #[derive(Subcommand)]
@@ -109,8 +275,8 @@ pub enum SourceData {
         limit: Option<u32>,
         #[arg(short, long, help = "allow to update exist item", default_value_t = false)]
         update: bool,
-        #[arg(short, long, help = "print verbose output")]
-        verbose: bool,
+        #[arg(long, action = ArgAction::Count, help = "print verbose output, same as the global -v")]
+        verbose: u8,
     }
 }
 
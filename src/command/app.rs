@@ -1,7 +1,7 @@
 use std::process::{Command, Stdio};
-use super::Context;
+use super::{Context, CommandError, CommandResult};
 
-pub fn start_app(context: &Context) {
+pub fn start_app(context: &Context) -> CommandResult {
     if let Some(application_path) = &context.config.work_path.application_path {
         let channel = context.channel_manager.current_channel();
         let args = if !context.config.debug_mode {
@@ -17,11 +17,13 @@ pub fn start_app(context: &Context) {
                 .stdout(Stdio::null())
                 .spawn() {
             Err(e) => {
-                eprintln!("Hedge app start failed. {}", e.to_string())
+                context.output.error("Hedge app start failed.", e);
+                Result::Err(CommandError::Failed)
             },
-            Ok(_) => {}
+            Ok(_) => Result::Ok(())
         }
     }else{
-        eprintln!("Config application_path is not configured.")
+        context.output.error("Config application_path is not configured.", "");
+        Result::Err(CommandError::Input)
     }
 }
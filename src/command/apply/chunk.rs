@@ -0,0 +1,196 @@
+use std::{error::Error, path::PathBuf, collections::HashMap, time::Duration};
+use serde::{Deserialize, Serialize};
+use crate::{module::{lifetime::LifetimeWatch, api::bulk::{BulkModule, BulkResult, SourceDataBulkForm, SourceDataIdentity, TagBulkForm, TopicBulkForm, AuthorBulkForm}}, utils::{error::ApplicationError, output::Output}};
+use super::ApplyFile;
+
+pub struct ChunkOptions {
+    pub chunk_size: usize,
+    pub retry: u32,
+    pub resume: bool
+}
+
+// 断点记录了每个部分已经提交成功的条目数。fingerprint取自合并后的apply内容，内容变化后断点即失效
+#[derive(Serialize, Deserialize, Default)]
+struct Checkpoint {
+    fingerprint: String,
+    completed: HashMap<String, usize>
+}
+
+pub struct Checkpointer {
+    path: PathBuf,
+    checkpoint: Checkpoint
+}
+
+impl Checkpointer {
+    pub fn load(output: &Output, path: PathBuf, file: &ApplyFile, resume: bool) -> Result<Checkpointer, Box<dyn Error>> {
+        let fingerprint = format!("{:016x}", fnv1a(serde_json::to_string(file)?.as_bytes()));
+
+        let previous: Option<Checkpoint> = if resume && path.exists() {
+            Option::Some(serde_json::from_str(&std::fs::read_to_string(&path)?)?)
+        }else{
+            Option::None
+        };
+        let checkpoint = match previous {
+            Some(c) if c.fingerprint == fingerprint => c,
+            Some(_) => {
+                output.message("Checkpoint of previous apply does not match current files. Start from the beginning.");
+                Checkpoint { fingerprint, completed: HashMap::new() }
+            },
+            None => Checkpoint { fingerprint, completed: HashMap::new() }
+        };
+        Result::Ok(Checkpointer { path, checkpoint })
+    }
+    fn completed(&self, section: &str) -> usize {
+        self.checkpoint.completed.get(section).copied().unwrap_or(0)
+    }
+    fn mark(&mut self, section: &str, completed: usize) -> Result<(), Box<dyn Error>> {
+        self.checkpoint.completed.insert(section.to_string(), completed);
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(&self.checkpoint)?)?;
+        Result::Ok(())
+    }
+    // 全部提交成功后删除断点；否则保留断点，下次执行相同的apply时从断点继续。返回断点是否被保留
+    pub fn finish(&self, success: bool) -> bool {
+        if success {
+            if self.path.exists() {
+                if let Err(e) = std::fs::remove_file(&self.path) {
+                    eprintln!("Cannot remove checkpoint {}. {}", self.path.to_string_lossy(), e);
+                }
+            }
+            false
+        }else{
+            self.path.exists()
+        }
+    }
+}
+
+// 64位FNV-1a。断点会被保存，摘要必须在不同的Rust版本之间保持一致，因此不使用标准库的DefaultHasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub(super) trait ChunkForm: Sized {
+    type Target;
+    async fn submit(bulk_module: &mut BulkModule<'_>, chunk: &[Self]) -> Result<BulkResult<Self::Target>, Box<dyn Error>>;
+}
+
+impl ChunkForm for SourceDataBulkForm {
+    type Target = SourceDataIdentity;
+    async fn submit(bulk_module: &mut BulkModule<'_>, chunk: &[Self]) -> Result<BulkResult<Self::Target>, Box<dyn Error>> {
+        bulk_module.source_data_bulk_update(chunk).await
+    }
+}
+
+impl ChunkForm for TagBulkForm {
+    type Target = String;
+    async fn submit(bulk_module: &mut BulkModule<'_>, chunk: &[Self]) -> Result<BulkResult<Self::Target>, Box<dyn Error>> {
+        bulk_module.tag_bulk_update(chunk).await
+    }
+}
+
+impl ChunkForm for TopicBulkForm {
+    type Target = String;
+    async fn submit(bulk_module: &mut BulkModule<'_>, chunk: &[Self]) -> Result<BulkResult<Self::Target>, Box<dyn Error>> {
+        bulk_module.topic_bulk_update(chunk).await
+    }
+}
+
+impl ChunkForm for AuthorBulkForm {
+    type Target = String;
+    async fn submit(bulk_module: &mut BulkModule<'_>, chunk: &[Self]) -> Result<BulkResult<Self::Target>, Box<dyn Error>> {
+        bulk_module.author_bulk_update(chunk).await
+    }
+}
+
+// 分块提交一个部分。tags/topics按顶层条目分块，子树随其顶层条目一起提交。
+// 请求失败的块会重试，重试耗尽或与server的连接丢失后停止此部分，已成功且没有条目失败的进度保存在断点中。
+pub(super) async fn submit_chunks<T: ChunkForm>(output: &Output, bulk_module: &mut BulkModule<'_>, lifetime: &LifetimeWatch, section: &str, items: &[T], options: &ChunkOptions, checkpointer: &mut Checkpointer) -> Result<BulkResult<T::Target>, Box<dyn Error>> {
+    let mut ret = BulkResult { success: 0, failed: 0, errors: Vec::new() };
+
+    let skipped = checkpointer.completed(section).min(items.len());
+    if skipped > 0 {
+        output.message(&format!("Bulk {}: skip {} item(s) submitted in previous run.", section, skipped));
+    }
+
+    let chunk_size = options.chunk_size.max(1);
+    let total = items[skipped..].len().div_ceil(chunk_size);
+    let mut completed = skipped;
+    // 含有条目级失败的块之后，断点不再前进，下次从该块开始重新提交，失败的条目不会被跳过
+    let mut clean = true;
+    for (index, chunk) in items[skipped..].chunks(chunk_size).enumerate() {
+        let mut attempt = 0;
+        let result = loop {
+            if let Some(reason) = lifetime.lost() {
+                return Result::Err(Box::new(ApplicationError::new(&format!("chunk {}/{} is not submitted because connection to server is lost. {}", index + 1, total, reason))))
+            }
+            match T::submit(bulk_module, chunk).await {
+                Ok(r) => break r,
+                Err(e) => if attempt < options.retry {
+                    attempt += 1;
+                    output.warning(&format!("Bulk {}: chunk {}/{} failed, retrying ({}/{}). {}", section, index + 1, total, attempt, options.retry, e));
+                    async_std::task::sleep(Duration::from_secs(attempt as u64)).await;
+                }else{
+                    return Result::Err(Box::new(ApplicationError::new(&format!("chunk {}/{} failed after {} retries. {}", index + 1, total, options.retry, e))))
+                }
+            }
+        };
+        completed += chunk.len();
+        clean = clean && result.failed == 0 && result.errors.is_empty();
+        if clean {
+            checkpointer.mark(section, completed)?;
+        }
+        if total > 1 {
+            output.message(&format!("Bulk {}: chunk {}/{} ({}/{} item(s)) submitted.", section, index + 1, total, completed, items.len()));
+        }
+        ret.success += result.success;
+        ret.failed += result.failed;
+        ret.errors.extend(result.errors);
+    }
+    Result::Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::utils::output::OutputFormat;
+    use super::*;
+
+    fn apply_file(value: serde_json::Value) -> ApplyFile {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn fnv1a_is_stable() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn checkpoint_resumes_only_for_same_content() {
+        let output = Output::new(OutputFormat::Json);
+        let dir = std::env::temp_dir().join(format!("hedge-cli-test-checkpoint-{}", std::process::id()));
+        let path = dir.join("default.json");
+        let file = apply_file(json!({ "tags": [{ "name": "a" }, { "name": "b" }] }));
+
+        let mut checkpointer = Checkpointer::load(&output, path.clone(), &file, true).unwrap();
+        checkpointer.mark("tags", 1).unwrap();
+        assert!(checkpointer.finish(false));
+
+        let checkpointer = Checkpointer::load(&output, path.clone(), &file, true).unwrap();
+        assert_eq!(checkpointer.completed("tags"), 1);
+        // 不使用--resume时从头开始
+        let checkpointer = Checkpointer::load(&output, path.clone(), &file, false).unwrap();
+        assert_eq!(checkpointer.completed("tags"), 0);
+        // 内容变化后断点失效
+        let changed = apply_file(json!({ "tags": [{ "name": "a" }, { "name": "c" }] }));
+        let checkpointer = Checkpointer::load(&output, path.clone(), &changed, true).unwrap();
+        assert_eq!(checkpointer.completed("tags"), 0);
+
+        assert!(!checkpointer.finish(true));
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
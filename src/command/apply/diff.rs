@@ -0,0 +1,315 @@
+use std::{error::Error, collections::{HashMap, HashSet}};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::{module::{server::ServerManager, api::{bulk::{TagBulkForm, TopicBulkForm, AuthorBulkForm, SourceDataBulkForm}, setting::SourceSiteUpdateForm, source_data::SourceDataModule}}, utils::error::ApiResultError};
+use crate::utils::output::Output;
+use super::{ApplyFile, ApplyFileSetting, prune::{PruneItem, print_prune_target}};
+use super::super::export::{fetch_apply_file, fetch_details, ExportParts};
+
+#[derive(PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffKind {
+    Create,
+    Update,
+    Unchanged,
+    Delete
+}
+
+#[derive(Serialize)]
+pub struct DiffItem {
+    pub kind: DiffKind,
+    pub target: String,
+    pub changes: Vec<DiffChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descendants: Option<usize>
+}
+
+#[derive(Serialize)]
+pub struct DiffChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value
+}
+
+pub async fn diff_apply_file(server_manager: &ServerManager, file: &ApplyFile) -> Result<Vec<DiffItem>, Box<dyn Error>> {
+    let parts = ExportParts { tags: file.tags.is_some(), topics: file.topics.is_some(), authors: file.authors.is_some(), setting: file.setting.is_some() };
+    let current = fetch_apply_file(server_manager, &parts).await?;
+
+    let mut ret: Vec<DiffItem> = Vec::new();
+    if let Some(source_data) = &file.source_data {
+        diff_source_data(server_manager, source_data, &mut ret).await?;
+    }
+    if let Some(tags) = &file.tags {
+        let declared: Vec<&TagBulkForm> = tags.iter().collect();
+        let current: Vec<&TagBulkForm> = current.tags.iter().flatten().collect();
+        diff_tags(Option::None, &declared, &current, &mut ret)?;
+    }
+    if let Some(topics) = &file.topics {
+        let mut current_map: HashMap<&str, &TopicBulkForm> = HashMap::new();
+        for topic in current.topics.iter().flatten() {
+            flatten_topic(topic, &mut current_map);
+        }
+        for topic in topics {
+            diff_topic(topic, &current_map, &mut ret)?;
+        }
+    }
+    if let Some(authors) = &file.authors {
+        let current_map: HashMap<&str, &AuthorBulkForm> = current.authors.iter().flatten().map(|a| (a.name.as_str(), a)).collect();
+        for author in authors {
+            ret.push(diff_entity(format!("author '{}'", author.name), author, current_map.get(author.name.as_str()).copied())?);
+        }
+    }
+    if let (Some(setting), Some(current)) = (&file.setting, &current.setting) {
+        diff_setting(setting, current, &mut ret)?;
+    }
+
+    Result::Ok(ret)
+}
+
+#[derive(Serialize)]
+struct DiffSummary {
+    create: usize,
+    update: usize,
+    delete: usize,
+    unchanged: usize
+}
+
+pub fn print_diff(output: &Output, items: Vec<DiffItem>, prune_items: &[PruneItem], verbose: bool) {
+    let mut items = items;
+    items.extend(prune_items.iter().map(|p| DiffItem { kind: DiffKind::Delete, target: p.target.clone(), changes: Vec::new(), descendants: Option::Some(p.descendants) }));
+
+    let count = |kind: DiffKind| items.iter().filter(|i| i.kind == kind).count();
+    let summary = DiffSummary { create: count(DiffKind::Create), update: count(DiffKind::Update), delete: count(DiffKind::Delete), unchanged: count(DiffKind::Unchanged) };
+
+    output.list(&items, &summary, |items, summary| {
+        for item in items {
+            match item.kind {
+                DiffKind::Create => println!("\x1b[1;32m+ {}\x1b[0m", item.target),
+                DiffKind::Update => println!("\x1b[1;33m~ {}\x1b[0m", item.target),
+                DiffKind::Unchanged => if verbose { println!("= {}", item.target) },
+                DiffKind::Delete => print_prune_target(&item.target, item.descendants.unwrap_or(0))
+            }
+            for change in &item.changes {
+                if item.kind == DiffKind::Create {
+                    println!("    {}: {}", change.field, change.after);
+                }else{
+                    println!("    {}: {} -> {}", change.field, change.before, change.after);
+                }
+            }
+        }
+
+        if summary.create > 0 || summary.update > 0 || summary.delete > 0 || verbose {
+            println!("---");
+        }
+        println!("Dry run: {} to create, {} to update, {} to delete, {} unchanged. Nothing is applied.", summary.create, summary.update, summary.delete, summary.unchanged);
+    });
+}
+
+// 列出已存在的source data时，每页的数量
+const LIST_PAGE_LIMIT: u32 = 1000;
+
+async fn diff_source_data(server_manager: &ServerManager, source_data: &[SourceDataBulkForm], ret: &mut Vec<DiffItem>) -> Result<(), Box<dyn Error>> {
+    let mut source_data_module = SourceDataModule::new(server_manager);
+    let existing = list_existing_source_data(&mut source_data_module, source_data).await?;
+    let source_data_module = &source_data_module;
+    let existing = &existing;
+    // 确定不存在的项无需请求；其余的项并发获取详情
+    let currents = fetch_details(source_data, |item| async move {
+        if existing.as_ref().is_some_and(|e| !e.contains(&(item.source_site.clone(), item.source_id))) {
+            return Result::Ok(Option::None)
+        }
+        match source_data_module.get(&item.source_site, item.source_id).await {
+            Ok(current) => Result::Ok(Option::Some(current.to_bulk_form())),
+            Err(e) => if e.downcast_ref::<ApiResultError>().is_some_and(|e| e.code == "NOT_FOUND") {
+                Result::Ok(Option::None)
+            }else{
+                Result::Err(format!("Get source data {}-{} failed. {}", item.source_site, item.source_id, e))
+            }
+        }
+    }).await?;
+    for (item, current) in source_data.iter().zip(currents) {
+        ret.push(diff_entity(format!("source-data {}-{}", item.source_site, item.source_id), item, current.as_ref())?);
+    }
+    Result::Ok(())
+}
+
+// 按site分页列出server上已存在的source data。列表的页数多于声明的项数时不值得列出，返回None，逐项获取
+async fn list_existing_source_data(source_data_module: &mut SourceDataModule<'_>, source_data: &[SourceDataBulkForm]) -> Result<Option<HashSet<(String, i64)>>, Box<dyn Error>> {
+    if source_data.is_empty() {
+        return Result::Ok(Option::None)
+    }
+    let sites: Vec<&str> = source_data.iter().map(|s| s.source_site.as_str()).collect::<HashSet<_>>().into_iter().collect();
+    let declared: HashSet<(&str, i64)> = source_data.iter().map(|s| (s.source_site.as_str(), s.source_id)).collect();
+    let mut existing: HashSet<(String, i64)> = HashSet::new();
+    let mut offset: u32 = 0;
+    loop {
+        let r = source_data_module.query(Option::None, Option::None, Option::Some(sites.clone()), Option::Some(offset), Option::Some(LIST_PAGE_LIMIT)).await?;
+        if offset == 0 && (r.total as usize).div_ceil(LIST_PAGE_LIMIT as usize) > source_data.len() {
+            return Result::Ok(Option::None)
+        }
+        let end = r.result.is_empty() || offset as usize + r.result.len() >= r.total as usize;
+        offset += r.result.len() as u32;
+        existing.extend(r.result.into_iter().filter(|s| declared.contains(&(s.site.as_str(), s.source_id))).map(|s| (s.site, s.source_id)));
+        if end {
+            return Result::Ok(Option::Some(existing))
+        }
+    }
+}
+
+fn diff_tags(parent: Option<&str>, declared: &[&TagBulkForm], current: &[&TagBulkForm], ret: &mut Vec<DiffItem>) -> Result<(), serde_json::Error> {
+    for tag in declared {
+        let address = if let Some(parent) = parent { format!("{}.{}", parent, tag.name) }else{ tag.name.clone() };
+        let current_tag = current.iter().find(|t| t.name == tag.name).copied();
+        ret.push(diff_entity(format!("tag '{}'", address), *tag, current_tag)?);
+        if let Some(children) = &tag.children {
+            let declared_children: Vec<&TagBulkForm> = children.iter().map(|c| c.as_ref()).collect();
+            let current_children: Vec<&TagBulkForm> = current_tag.and_then(|t| t.children.as_ref()).iter().flat_map(|c| c.iter()).map(|c| c.as_ref()).collect();
+            diff_tags(Option::Some(&address), &declared_children, &current_children, ret)?;
+        }
+    }
+    Result::Ok(())
+}
+
+fn flatten_topic<'t>(topic: &'t TopicBulkForm, ret: &mut HashMap<&'t str, &'t TopicBulkForm>) {
+    ret.entry(topic.name.as_str()).or_insert(topic);
+    if let Some(children) = &topic.children {
+        for child in children {
+            flatten_topic(child, ret);
+        }
+    }
+}
+
+fn diff_topic(topic: &TopicBulkForm, current_map: &HashMap<&str, &TopicBulkForm>, ret: &mut Vec<DiffItem>) -> Result<(), serde_json::Error> {
+    ret.push(diff_entity(format!("topic '{}'", topic.name), topic, current_map.get(topic.name.as_str()).copied())?);
+    if let Some(children) = &topic.children {
+        for child in children {
+            diff_topic(child, current_map, ret)?;
+        }
+    }
+    Result::Ok(())
+}
+
+fn diff_setting(setting: &ApplyFileSetting, current: &ApplyFileSetting, ret: &mut Vec<DiffItem>) -> Result<(), serde_json::Error> {
+    if let Some(meta) = &setting.meta {
+        ret.push(diff_entity("setting.meta".to_string(), meta, current.meta.as_ref())?);
+    }
+    if let Some(query) = &setting.query {
+        ret.push(diff_entity("setting.query".to_string(), query, current.query.as_ref())?);
+    }
+    if let Some(find_similar) = &setting.find_similar {
+        ret.push(diff_entity("setting.find_similar".to_string(), find_similar, current.find_similar.as_ref())?);
+    }
+    if let Some(file) = &setting.file {
+        ret.push(diff_entity("setting.file".to_string(), file, current.file.as_ref())?);
+    }
+    if let Some(sites) = &setting.source_sites {
+        let current_map: HashMap<&str, &SourceSiteUpdateForm> = current.source_sites.iter().flatten().map(|s| (s.name.as_str(), s)).collect();
+        for site in sites {
+            ret.push(diff_entity(format!("setting.source_sites '{}'", site.name), site, current_map.get(site.name.as_str()).copied())?);
+        }
+    }
+    if let Some(import) = &setting.import {
+        ret.push(diff_entity("setting.import".to_string(), import, current.import.as_ref())?);
+    }
+    Result::Ok(())
+}
+
+// 比较一项声明与服务器上的当前值。bulk与setting都是部分更新，因此只比较声明了的字段；children由调用方单独递归。
+fn diff_entity<T: Serialize>(target: String, declared: &T, current: Option<&T>) -> Result<DiffItem, serde_json::Error> {
+    let declared = to_fields(declared)?;
+    let current = if let Some(c) = current { Option::Some(to_fields(c)?) }else{ Option::None };
+
+    let mut changes: Vec<DiffChange> = Vec::new();
+    for (field, after) in declared {
+        if field == "name" || field == "sourceSite" || field == "sourceId" {
+            continue
+        }
+        if field == "rename" {
+            let before = current.as_ref().and_then(|c| c.get("name")).cloned().unwrap_or(Value::Null);
+            if before != after {
+                changes.push(DiffChange { field: "name".to_string(), before, after });
+            }
+            continue
+        }
+        let before = current.as_ref().and_then(|c| c.get(&field)).cloned().unwrap_or(Value::Null);
+        if current.is_none() || !value_eq(&before, &after) {
+            changes.push(DiffChange { field, before, after });
+        }
+    }
+
+    let kind = if current.is_none() { DiffKind::Create }else if changes.is_empty() { DiffKind::Unchanged }else{ DiffKind::Update };
+    Result::Ok(DiffItem { kind, target, changes, descendants: Option::None })
+}
+
+fn to_fields<T: Serialize>(value: &T) -> Result<Map<String, Value>, serde_json::Error> {
+    match serde_json::to_value(value)? {
+        Value::Object(mut map) => {
+            map.remove("children");
+            Result::Ok(map)
+        },
+        _ => Result::Ok(Map::new())
+    }
+}
+
+fn value_eq(a: &Value, b: &Value) -> bool {
+    a == b || (is_empty_value(a) && is_empty_value(b))
+}
+
+fn is_empty_value(v: &Value) -> bool {
+    match v {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn tags(value: Value) -> Vec<TagBulkForm> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn diff_entity_compares_only_declared_fields() {
+        let declared = tags(json!([{ "name": "t", "color": "red" }]));
+        let current = tags(json!([{ "name": "t", "color": "red", "description": "d" }]));
+        let item = diff_entity("t".to_string(), &declared[0], Option::Some(&current[0])).unwrap();
+        assert!(item.kind == DiffKind::Unchanged);
+
+        let declared = tags(json!([{ "name": "t", "color": "blue", "links": [] }]));
+        let item = diff_entity("t".to_string(), &declared[0], Option::Some(&current[0])).unwrap();
+        assert!(item.kind == DiffKind::Update);
+        // 空列表与未设置视为相同
+        assert_eq!(item.changes.iter().map(|c| c.field.as_str()).collect::<Vec<_>>(), vec!["color"]);
+        assert_eq!(item.changes[0].before, json!("red"));
+        assert_eq!(item.changes[0].after, json!("blue"));
+    }
+
+    #[test]
+    fn diff_entity_reports_rename_as_name_change() {
+        let declared = tags(json!([{ "name": "old", "rename": "new" }]));
+        let current = tags(json!([{ "name": "old" }]));
+        let item = diff_entity("t".to_string(), &declared[0], Option::Some(&current[0])).unwrap();
+        assert!(item.kind == DiffKind::Update);
+        assert_eq!(item.changes[0].field, "name");
+        assert_eq!(item.changes[0].before, json!("old"));
+        assert_eq!(item.changes[0].after, json!("new"));
+    }
+
+    #[test]
+    fn diff_tags_walks_children() {
+        let declared = tags(json!([{ "name": "p", "children": [{ "name": "c1" }, { "name": "c2", "color": "red" }] }, { "name": "q" }]));
+        let current = tags(json!([{ "name": "p", "children": [{ "name": "c2" }] }]));
+        let declared: Vec<&TagBulkForm> = declared.iter().collect();
+        let current: Vec<&TagBulkForm> = current.iter().collect();
+        let mut ret = Vec::new();
+        diff_tags(Option::None, &declared, &current, &mut ret).unwrap();
+        let kinds: Vec<(&str, &DiffKind)> = ret.iter().map(|i| (i.target.as_str(), &i.kind)).collect();
+        assert!(kinds == vec![("tag 'p'", &DiffKind::Unchanged), ("tag 'p.c1'", &DiffKind::Create), ("tag 'p.c2'", &DiffKind::Update), ("tag 'q'", &DiffKind::Create)]);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use clap::ValueEnum;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use crate::{module::api::bulk::{SourceDataBulkForm, TagBulkForm, TopicBulkForm, AuthorBulkForm}, utils::error::ApplicationError};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergeStrategy {
+    Fail,
+    Deep
+}
+
+// 每一项都带有它来源的文件名
+pub type Sourced<T> = (String, T);
+
+pub fn merge_source_data(items: Vec<Sourced<SourceDataBulkForm>>, strategy: MergeStrategy) -> Result<Vec<SourceDataBulkForm>, ApplicationError> {
+    let items = to_values(items)?;
+    let merged = merge_list("source-data", Option::None, items, source_data_key, false, strategy)?;
+    from_values(merged)
+}
+
+pub fn merge_tags(items: Vec<Sourced<TagBulkForm>>, strategy: MergeStrategy) -> Result<Vec<TagBulkForm>, ApplicationError> {
+    let items = to_values(items)?;
+    let merged = merge_list("tag", Option::None, items, name_key, true, strategy)?;
+    from_values(merged)
+}
+
+pub fn merge_topics(items: Vec<Sourced<TopicBulkForm>>, strategy: MergeStrategy) -> Result<Vec<TopicBulkForm>, ApplicationError> {
+    let items = to_values(items)?;
+    // topic的name是全局唯一的。同一位置上的重复可以合并，不同位置上的同名topic无论如何都是冲突
+    let mut positions: HashMap<String, (String, String)> = HashMap::new();
+    for (source, value) in &items {
+        check_topic_position(Option::None, source, value, &mut positions)?;
+    }
+    let merged = merge_list("topic", Option::None, items, name_key, true, strategy)?;
+    from_values(merged)
+}
+
+pub fn merge_authors(items: Vec<Sourced<AuthorBulkForm>>, strategy: MergeStrategy) -> Result<Vec<AuthorBulkForm>, ApplicationError> {
+    let items = to_values(items)?;
+    let merged = merge_list("author", Option::None, items, name_key, false, strategy)?;
+    from_values(merged)
+}
+
+struct MergeEntry {
+    key: String,
+    source: String,
+    fields: Map<String, Value>,
+    children: Vec<Sourced<Value>>
+}
+
+// 在同一层级中按key合并。fail策略下重复即报错；deep策略下合并字段，同一字段的值不同时报错，children继续向下合并
+fn merge_list(kind: &str, parent: Option<&str>, items: Vec<Sourced<Value>>, key_of: fn(&Map<String, Value>) -> String, nested: bool, strategy: MergeStrategy) -> Result<Vec<Value>, ApplicationError> {
+    let mut entries: Vec<MergeEntry> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (source, value) in items {
+        let mut fields = if let Value::Object(map) = value { map }else{ Map::new() };
+        let children: Vec<Sourced<Value>> = match fields.remove("children") {
+            Some(Value::Array(children)) => children.into_iter().map(|c| (source.clone(), c)).collect(),
+            _ => Vec::new()
+        };
+        let key = key_of(&fields);
+        if let Some(&i) = index.get(&key) {
+            let entry = &mut entries[i];
+            let address = address_of(parent, &key);
+            if strategy == MergeStrategy::Fail {
+                return Result::Err(ApplicationError::new(&format!("{} '{}' is declared in both {} and {}. Use --merge deep to merge them.", kind, address, entry.source, source)))
+            }
+            for (field, value) in fields {
+                match entry.fields.get(&field) {
+                    Some(existing) if existing != &value => return Result::Err(ApplicationError::new(&format!("{} '{}' is declared in both {} and {} with different '{}'.", kind, address, entry.source, source, field))),
+                    Some(_) => {},
+                    None => { entry.fields.insert(field, value); }
+                }
+            }
+            entry.children.extend(children);
+        }else{
+            index.insert(key.clone(), entries.len());
+            entries.push(MergeEntry { key, source, fields, children });
+        }
+    }
+
+    let mut ret: Vec<Value> = Vec::with_capacity(entries.len());
+    for mut entry in entries {
+        if nested && !entry.children.is_empty() {
+            let address = address_of(parent, &entry.key);
+            let children = merge_list(kind, Option::Some(&address), entry.children, key_of, nested, strategy)?;
+            entry.fields.insert("children".to_string(), Value::Array(children));
+        }
+        ret.push(Value::Object(entry.fields));
+    }
+    Result::Ok(ret)
+}
+
+fn check_topic_position(parent: Option<&str>, source: &str, value: &Value, positions: &mut HashMap<String, (String, String)>) -> Result<(), ApplicationError> {
+    let name = value.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+    let parent_address = parent.unwrap_or_default().to_string();
+    if let Some((existing_parent, existing_source)) = positions.get(name) {
+        if existing_parent != &parent_address {
+            return Result::Err(ApplicationError::new(&format!("topic '{}' is declared at different positions in {} and {}.", name, existing_source, source)))
+        }
+    }else{
+        positions.insert(name.to_string(), (parent_address, source.to_string()));
+    }
+    let address = address_of(parent, name);
+    for child in value.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        check_topic_position(Option::Some(&address), source, child, positions)?;
+    }
+    Result::Ok(())
+}
+
+fn name_key(fields: &Map<String, Value>) -> String {
+    fields.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string()
+}
+
+fn source_data_key(fields: &Map<String, Value>) -> String {
+    let site = fields.get("sourceSite").and_then(|n| n.as_str()).unwrap_or_default();
+    let id = fields.get("sourceId").map(|n| n.to_string()).unwrap_or_default();
+    format!("{}-{}", site, id)
+}
+
+fn address_of(parent: Option<&str>, key: &str) -> String {
+    if let Some(parent) = parent { format!("{}.{}", parent, key) }else{ key.to_string() }
+}
+
+fn to_values<T: Serialize>(items: Vec<Sourced<T>>) -> Result<Vec<Sourced<Value>>, ApplicationError> {
+    items.into_iter().map(|(source, item)| match serde_json::to_value(item) {
+        Ok(v) => Result::Ok((source, v)),
+        Err(e) => Result::Err(ApplicationError::new(&e.to_string()))
+    }).collect()
+}
+
+fn from_values<T: DeserializeOwned>(values: Vec<Value>) -> Result<Vec<T>, ApplicationError> {
+    values.into_iter().map(|v| serde_json::from_value(v).map_err(|e| ApplicationError::new(&e.to_string()))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn sourced<T: DeserializeOwned>(source: &str, value: Value) -> Vec<Sourced<T>> {
+        let items: Vec<T> = serde_json::from_value(value).unwrap();
+        items.into_iter().map(|i| (source.to_string(), i)).collect()
+    }
+
+    #[test]
+    fn fail_strategy_rejects_duplicates() {
+        let mut tags = sourced::<TagBulkForm>("a.yaml", json!([{ "name": "t" }]));
+        tags.extend(sourced("b.yaml", json!([{ "name": "t" }])));
+        let e = merge_tags(tags, MergeStrategy::Fail).err().unwrap();
+        assert!(e.to_string().contains("a.yaml and b.yaml"), "{}", e);
+    }
+
+    #[test]
+    fn deep_strategy_merges_fields_and_children() {
+        let mut tags = sourced::<TagBulkForm>("a.yaml", json!([{ "name": "t", "color": "red", "children": [{ "name": "c1" }] }]));
+        tags.extend(sourced("b.yaml", json!([{ "name": "t", "description": "d", "children": [{ "name": "c1", "color": "blue" }, { "name": "c2" }] }])));
+        let merged = merge_tags(tags, MergeStrategy::Deep).unwrap();
+        assert_eq!(serde_json::to_value(&merged).unwrap(), json!([
+            { "name": "t", "color": "red", "description": "d", "children": [{ "name": "c1", "color": "blue" }, { "name": "c2" }] }
+        ]));
+    }
+
+    #[test]
+    fn deep_strategy_rejects_conflicting_fields() {
+        let mut tags = sourced::<TagBulkForm>("a.yaml", json!([{ "name": "t", "children": [{ "name": "c", "color": "red" }] }]));
+        tags.extend(sourced("b.yaml", json!([{ "name": "t", "children": [{ "name": "c", "color": "blue" }] }])));
+        let e = merge_tags(tags, MergeStrategy::Deep).err().unwrap();
+        assert!(e.to_string().contains("'t.c'") && e.to_string().contains("'color'"), "{}", e);
+    }
+
+    #[test]
+    fn topics_at_different_positions_conflict() {
+        let mut topics = sourced::<TopicBulkForm>("a.yaml", json!([{ "name": "p1", "children": [{ "name": "t" }] }]));
+        topics.extend(sourced("b.yaml", json!([{ "name": "p2", "children": [{ "name": "t" }] }])));
+        assert!(merge_topics(topics, MergeStrategy::Deep).is_err());
+
+        let mut topics = sourced::<TopicBulkForm>("a.yaml", json!([{ "name": "p1", "children": [{ "name": "t" }] }]));
+        topics.extend(sourced("b.yaml", json!([{ "name": "p1", "children": [{ "name": "t" }] }])));
+        assert_eq!(merge_topics(topics, MergeStrategy::Deep).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn authors_and_source_data_merge_by_key() {
+        let mut authors = sourced::<AuthorBulkForm>("a.yaml", json!([{ "name": "x" }, { "name": "y" }]));
+        authors.extend(sourced("b.yaml", json!([{ "name": "x" }])));
+        assert_eq!(merge_authors(authors, MergeStrategy::Deep).unwrap().len(), 2);
+
+        let mut source_data = sourced::<SourceDataBulkForm>("a.yaml", json!([{ "sourceSite": "s", "sourceId": 1 }, { "sourceSite": "s", "sourceId": 2 }]));
+        source_data.extend(sourced("b.yaml", json!([{ "sourceSite": "s", "sourceId": 1 }])));
+        assert_eq!(merge_source_data(source_data, MergeStrategy::Deep).unwrap().len(), 2);
+    }
+}
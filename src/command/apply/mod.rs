@@ -0,0 +1,516 @@
+use std::{path::{Path, PathBuf}, io::stdin, error::Error, collections::{HashMap, HashSet}};
+use glob::Pattern;
+use serde_json::{Map, Value};
+use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use crate::{module::api::{bulk::{BulkModule, BulkResult, SourceDataBulkForm, TagBulkForm, TopicBulkForm, AuthorBulkForm}, setting::{MetaOptionUpdateForm, QueryOptionUpdateForm, ImportOptionUpdateForm, StorageOptionUpdateForm, FindSimilarOptionUpdateForm, SettingModule, SourceSiteUpdateForm}}, utils::error::ApplicationError};
+use super::{Context, CommandError, CommandResult};
+pub use chunk::ChunkOptions;
+use chunk::Checkpointer;
+use merge::{MergeStrategy, Sourced};
+use prune::PruneKind;
+use parse::ApplyFileError;
+use template::Vars;
+
+mod chunk;
+mod diff;
+mod parse;
+pub mod merge;
+mod template;
+pub mod prune;
+pub mod validate;
+
+pub enum ApplyInputType {
+    Directory(PathBuf),
+    File(PathBuf),
+    Input
+}
+
+pub struct ApplyOptions {
+    pub verbose: bool,
+    pub dry_run: bool,
+    pub prune: Vec<PruneKind>,
+    pub yes: bool,
+    pub globs: Vec<Pattern>,
+    pub vars: Vec<String>,
+    pub merge: MergeStrategy,
+    pub chunk: ChunkOptions
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ApplyItem {
+    Bulk { section: &'static str, success: i32, failed: i32, errors: Vec<ApplyItemError>, error: Option<String> },
+    Setting { section: &'static str, error: Option<String> },
+    Prune { target: String, error: Option<String> }
+}
+
+#[derive(Serialize)]
+struct ApplyItemError {
+    target: String,
+    code: String,
+    message: String
+}
+
+#[derive(Serialize)]
+struct ApplySummary {
+    completed: bool,
+    prune_success: usize,
+    prune_failed: usize,
+    prune_skipped: usize,
+    checkpoint_saved: bool
+}
+
+//TODO 替换enum
+pub async fn apply(context: &mut Context<'_>, input: &Vec<ApplyInputType>, options: &ApplyOptions) -> CommandResult {
+    let verbose = options.verbose;
+    if !options.prune.is_empty() && !options.dry_run && !options.yes {
+        if input.iter().any(|i| matches!(i, ApplyInputType::Input)) {
+            context.output.error("Option --prune cannot ask for confirmation when reading from stdin. Use --yes to confirm it in advance.", "");
+            return Result::Err(CommandError::Input)
+        }else if !context.output.is_text() {
+            context.output.error("Option --prune cannot ask for confirmation when output is not text. Use --yes to confirm it in advance.", "");
+            return Result::Err(CommandError::Input)
+        }
+    }
+
+    let vars = match template::parse_vars(&options.vars) {
+        Ok(v) => v,
+        Err(e) => {
+            context.output.error(&e.to_string(), "");
+            return Result::Err(CommandError::Input)
+        }
+    };
+
+    if let Err(e) = context.server_manager.maintaining_for_start().await {
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
+    }
+
+    let file = match read_input(input, &options.globs, &vars, options.merge) {
+        Ok(f) => f,
+        Err(e) => {
+            context.output.error("Apply input read error.", e);
+            return Result::Err(CommandError::Input)
+        }
+    };
+
+    let prune_items = if options.prune.is_empty() { Vec::new() }else{
+        match prune::plan_prune(context.server_manager, &file, &options.prune).await {
+            Ok(items) => items,
+            Err(e) => {
+                context.output.error("Prune planning failed.", &e);
+                return Result::Err(CommandError::of_request(e.as_ref()))
+            }
+        }
+    };
+
+    if options.dry_run {
+        return match diff::diff_apply_file(context.server_manager, &file).await {
+            Err(e) => {
+                context.output.error("Error occrred in requesting.", &e);
+                Result::Err(CommandError::of_request(e.as_ref()))
+            },
+            Ok(items) => {
+                diff::print_diff(&context.output, items, &prune_items, verbose);
+                Result::Ok(())
+            }
+        }
+    }
+
+    if !prune_items.is_empty() && context.output.is_text() {
+        prune::print_prune(&prune_items);
+        if !options.yes && !prune::confirm_prune(&prune_items) {
+            println!("Apply is cancelled.");
+            return Result::Ok(())
+        }
+    }
+
+    let checkpoint_path = context.config.work_path.userdata_path.join("cli/checkpoint").join(format!("{}.json", context.channel_manager.current_channel()));
+    let mut checkpointer = match Checkpointer::load(&context.output, checkpoint_path, &file, options.chunk.resume) {
+        Ok(c) => c,
+        Err(e) => {
+            context.output.error("Cannot load apply checkpoint.", e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+
+    let output = &context.output;
+    let mut items: Vec<ApplyItem> = Vec::new();
+    let emit = |items: &mut Vec<ApplyItem>, item: ApplyItem| {
+        output.item(&item, |i| print_apply_item(i, verbose));
+        items.push(item);
+    };
+
+    let lifetime = context.server_manager.lifetime_watch();
+    let mut bulk_module = BulkModule::new(context.server_manager);
+
+    if let Some(source_data) = file.source_data {
+        let result = chunk::submit_chunks(output, &mut bulk_module, &lifetime, "source-data", &source_data, &options.chunk, &mut checkpointer).await;
+        emit(&mut items, bulk_item("source-data", result, |t| format!("{}-{}", t.source_site, t.source_id)));
+    }
+    if let Some(tags) = file.tags {
+        let result = chunk::submit_chunks(output, &mut bulk_module, &lifetime, "tags", &tags, &options.chunk, &mut checkpointer).await;
+        emit(&mut items, bulk_item("tags", result, |t| t.clone()));
+    }
+    if let Some(topics) = file.topics {
+        let result = chunk::submit_chunks(output, &mut bulk_module, &lifetime, "topics", &topics, &options.chunk, &mut checkpointer).await;
+        emit(&mut items, bulk_item("topics", result, |t| t.clone()));
+    }
+    if let Some(authors) = file.authors {
+        let result = chunk::submit_chunks(output, &mut bulk_module, &lifetime, "authors", &authors, &options.chunk, &mut checkpointer).await;
+        emit(&mut items, bulk_item("authors", result, |t| t.clone()));
+    }
+    // 连接已丢失时不再提交setting
+    if let Some(setting) = file.setting.filter(|_| lifetime.lost().is_none()) {
+        let mut setting_module = SettingModule::new(context.server_manager);
+
+        if let Some(b) = setting.meta {
+            emit(&mut items, setting_item("meta", setting_module.set_meta_option(&b).await));
+        }
+        if let Some(b) = setting.query {
+            emit(&mut items, setting_item("query", setting_module.set_query_option(&b).await));
+        }
+        if let Some(b) = setting.find_similar {
+            emit(&mut items, setting_item("find_similar", setting_module.set_find_similar_option(&b).await));
+        }
+        if let Some(b) = setting.file {
+            emit(&mut items, setting_item("file", setting_module.set_storage_option(&b).await));
+        }
+        if let Some(b) = setting.source_sites {
+            emit(&mut items, setting_item("source_sites", setting_module.set_source_sites(&b).await));
+        }
+        if let Some(b) = setting.import {
+            emit(&mut items, setting_item("import", setting_module.set_import_option(&b).await));
+        }
+    }
+    // apply文件描述完整的期望状态，bulk没有全部完成时，删除未声明的实体可能误删本应被替换或移动的实体
+    let lost = lifetime.lost();
+    let completed = lost.is_none() && !items.iter().any(|i| matches!(i, ApplyItem::Bulk { error: Some(_), .. }));
+    let prune_skipped = if completed { 0 }else{ prune_items.len() };
+    if completed && !prune_items.is_empty() {
+        let results = prune::execute_prune(context.server_manager, &prune_items).await;
+        for (item, error) in prune_items.iter().zip(results) {
+            emit(&mut items, ApplyItem::Prune { target: item.target.clone(), error });
+        }
+    }
+
+    let prune_failed = items.iter().filter(|i| matches!(i, ApplyItem::Prune { error: Some(_), .. })).count();
+    let summary = ApplySummary { completed, prune_success: prune_items.len() - prune_skipped - prune_failed, prune_failed, prune_skipped, checkpoint_saved: checkpointer.finish(completed) };
+    output.summary(&items, &summary, |s| {
+        if s.prune_skipped > 0 {
+            println!("Prune: skipped {} item(s) because apply is not completed.", s.prune_skipped);
+        }else if !prune_items.is_empty() {
+            println!("Prune: {} succeed, {} failed.", s.prune_success, s.prune_failed);
+        }
+        if s.checkpoint_saved {
+            println!("Checkpoint is saved. Run the same apply again to continue from where it stopped.");
+        }
+    });
+    if let Some(reason) = lost {
+        output.error("Lost connection to server.", reason);
+        return Result::Err(CommandError::Connection)
+    }
+    let failed = items.iter().filter(|i| match i {
+        ApplyItem::Bulk { failed, errors, error, .. } => error.is_some() || *failed > 0 || !errors.is_empty(),
+        ApplyItem::Setting { error, .. } | ApplyItem::Prune { error, .. } => error.is_some()
+    }).count();
+    CommandError::of_failed(failed)
+}
+
+fn bulk_item<I>(section: &'static str, result: Result<BulkResult<I>, Box<dyn Error>>, target_of: impl Fn(&I) -> String) -> ApplyItem {
+    match result {
+        Err(e) => ApplyItem::Bulk { section, success: 0, failed: 0, errors: Vec::new(), error: Option::Some(e.to_string()) },
+        Ok(r) => {
+            let errors = r.errors.iter().map(|e| ApplyItemError { target: target_of(&e.target), code: e.error.code.clone(), message: e.error.message.clone() }).collect();
+            ApplyItem::Bulk { section, success: r.success, failed: r.failed, errors, error: Option::None }
+        }
+    }
+}
+
+fn setting_item(section: &'static str, result: Result<(), Box<dyn Error>>) -> ApplyItem {
+    ApplyItem::Setting { section, error: result.err().map(|e| e.to_string()) }
+}
+
+fn print_apply_item(item: &ApplyItem, verbose: bool) {
+    match item {
+        ApplyItem::Bulk { section, error: Some(e), .. } => eprintln!("Bulk {} failed. {}", section, e),
+        ApplyItem::Bulk { section, success, failed, errors, error: None } => {
+            println!("Bulk {}: {} succeed, {} failed, {} errors.", section, success, failed, errors.len());
+            if verbose && !errors.is_empty() {
+                println!("---");
+                for e in errors {
+                    println!("Error {}: [{}]{}", e.target, e.code, e.message);
+                }
+                println!();
+            }
+        },
+        ApplyItem::Setting { section, error: Some(e) } => eprintln!("Update setting.{} failed. {}", section, e),
+        ApplyItem::Setting { section, error: None } => println!("Update setting.{} succeed.", section),
+        ApplyItem::Prune { target, error: Some(e) } => eprintln!("Delete {} failed. {}", target, e),
+        ApplyItem::Prune { error: None, .. } => {}
+    }
+}
+
+fn read_input(input: &Vec<ApplyInputType>, globs: &[Pattern], vars: &Vars, merge: MergeStrategy) -> Result<ApplyFile, Box<dyn Error>> {
+    let mut reader = ApplyReader::new(globs, false);
+    reader.read(input, vars)?;
+    if reader.files.is_empty() {
+        Result::Err(Box::new(ApplicationError::new("apply files is empty.")))
+    }else{
+        Result::Ok(reduce_apply_files(reader.files, merge)?)
+    }
+}
+
+struct ApplyReader<'t> {
+    globs: &'t [Pattern],
+    visited: HashSet<PathBuf>,
+    files: Vec<Sourced<ApplyFile>>,
+    // validate模式下，单个文件的错误会被收集起来而不是中止读取
+    errors: Option<Vec<Box<dyn Error>>>
+}
+
+impl<'t> ApplyReader<'t> {
+    fn new(globs: &'t [Pattern], collect_errors: bool) -> Self {
+        ApplyReader { globs, visited: HashSet::new(), files: Vec::new(), errors: if collect_errors { Option::Some(Vec::new()) }else{ Option::None } }
+    }
+    fn read(&mut self, input: &Vec<ApplyInputType>, vars: &Vars) -> Result<(), Box<dyn Error>> {
+        for i in input {
+            match i {
+                ApplyInputType::Directory(d) => self.read_from_directory(d, d, vars)?,
+                ApplyInputType::File(f) => self.read_from_file(f, vars)?,
+                ApplyInputType::Input => {
+                    let text = read_from_input()?;
+                    let result = self.read_text("<stdin>", parse::detect_file_type(&text), &text, Path::new("."), vars);
+                    self.collect(result)?
+                }
+            }
+        }
+        Result::Ok(())
+    }
+    fn collect(&mut self, result: Result<(), Box<dyn Error>>) -> Result<(), Box<dyn Error>> {
+        match (result, &mut self.errors) {
+            (Err(e), Some(errors)) => {
+                errors.push(e);
+                Result::Ok(())
+            },
+            (result, _) => result
+        }
+    }
+    fn read_from_directory(&mut self, root: &Path, d: &Path, vars: &Vars) -> Result<(), Box<dyn Error>> {
+        // 符号链接会被跟随，记录已读取的目录以避免链接成环
+        if !self.visited.insert(d.canonicalize()?) {
+            return Result::Ok(())
+        }
+        let mut entries = std::fs::read_dir(d)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let file_name = entry.file_name();
+            if file_name.to_string_lossy().starts_with('.') {
+                continue
+            }
+            let path = entry.path();
+            let file_type = std::fs::metadata(&path).map_err(|e| ApplicationError::new(&format!("Cannot read {}: {}", path.to_string_lossy(), e)))?;
+            if file_type.is_dir() {
+                self.read_from_directory(root, &path, vars)?
+            }else if file_type.is_file() && get_file_type(&path).is_some() && self.match_globs(root, &path) {
+                self.read_from_file(&path, vars)?
+            }
+        }
+        Result::Ok(())
+    }
+    fn read_from_file(&mut self, f: &Path, vars: &Vars) -> Result<(), Box<dyn Error>> {
+        // 同一文件可能同时被目录与include引用，只读取一次，这也避免了include的循环引用
+        if !self.visited.insert(f.canonicalize()?) {
+            return Result::Ok(())
+        }
+        let file_type = if let Some(t) = get_file_type(f) { t }else{
+            return Result::Err(Box::new(ApplicationError::new(&format!("Unsupported file type of {}.", f.to_string_lossy()))))
+        };
+        let text = std::fs::read_to_string(f)?;
+        let result = self.read_text(&f.to_string_lossy(), file_type, &text, f.parent().unwrap_or(Path::new(".")), vars);
+        self.collect(result)
+    }
+    fn read_text(&mut self, name: &str, file_type: &str, text: &str, base: &Path, vars: &Vars) -> Result<(), Box<dyn Error>> {
+        let (mut file, vars) = parse::parse_apply_file(name, file_type, text, vars)?;
+        let include = file.include.take();
+        self.files.push((name.to_string(), file));
+        if let Some(include) = include {
+            self.read_include(name, base, &include, &vars)?;
+        }
+        Result::Ok(())
+    }
+    fn read_include(&mut self, name: &str, base: &Path, include: &Vec<String>, vars: &Vars) -> Result<(), Box<dyn Error>> {
+        for pattern in include {
+            let full_pattern = base.join(pattern);
+            let full_pattern = full_pattern.to_string_lossy();
+            let mut matched: Vec<PathBuf> = glob::glob(&full_pattern)?.collect::<Result<_, _>>()?;
+            if matched.is_empty() && !pattern.contains(['*', '?', '[']) {
+                return Result::Err(Box::new(ApplyFileError { file: name.to_string(), line_column: Option::None, path: Option::Some("include".to_string()), message: format!("Included file {} is not found.", full_pattern) }))
+            }
+            matched.sort();
+            for path in matched {
+                if path.is_dir() {
+                    self.read_from_directory(&path, &path, vars)?
+                }else{
+                    self.read_from_file(&path, vars)?
+                }
+            }
+        }
+        Result::Ok(())
+    }
+    fn match_globs(&self, root: &Path, path: &Path) -> bool {
+        if self.globs.is_empty() {
+            return true
+        }
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let file_name = Path::new(path.file_name().unwrap_or_default());
+        // 不含路径分隔符的模式只匹配文件名，以使"*.yaml"这类模式作用于所有子目录
+        self.globs.iter().any(|g| if g.as_str().contains('/') { g.matches_path(relative) }else{ g.matches_path(file_name) })
+    }
+}
+
+fn get_file_type(f: &Path) -> Option<&'static str> {
+    match f.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "json" => Option::Some("json"),
+        "yaml" => Option::Some("yaml"),
+        "toml" => Option::Some("toml"),
+        _ => Option::None
+    }
+}
+
+fn read_from_input() -> Result<String, Box<dyn Error>> {
+    let lines: Vec<String> = stdin().lines().collect::<Result<_, _>>()?;
+    Result::Ok(lines.join("\n"))
+}
+
+fn reduce_apply_files(files: Vec<Sourced<ApplyFile>>, strategy: MergeStrategy) -> Result<ApplyFile, Box<dyn Error>> {
+    let mut ret_source_data: Vec<Sourced<SourceDataBulkForm>> = Vec::new();
+    let mut ret_tags: Vec<Sourced<TagBulkForm>> = Vec::new();
+    let mut ret_topics: Vec<Sourced<TopicBulkForm>> = Vec::new();
+    let mut ret_authors: Vec<Sourced<AuthorBulkForm>> = Vec::new();
+    let mut ret_settings: Vec<Sourced<ApplyFileSetting>> = Vec::new();
+
+    for (source, f) in files {
+        if let Some(source_data) = f.source_data {
+            ret_source_data.extend(source_data.into_iter().map(|i| (source.clone(), i)))
+        }
+        if let Some(tags) = f.tags {
+            ret_tags.extend(tags.into_iter().map(|i| (source.clone(), i)))
+        }
+        if let Some(topics) = f.topics {
+            ret_topics.extend(topics.into_iter().map(|i| (source.clone(), i)))
+        }
+        if let Some(authors) = f.authors {
+            ret_authors.extend(authors.into_iter().map(|i| (source.clone(), i)))
+        }
+        if let Some(setting) = f.setting {
+            ret_settings.push((source, setting))
+        }
+    }
+
+    let source_data = merge::merge_source_data(ret_source_data, strategy)?;
+    let tags = merge::merge_tags(ret_tags, strategy)?;
+    let topics = merge::merge_topics(ret_topics, strategy)?;
+    let authors = merge::merge_authors(ret_authors, strategy)?;
+
+    Result::Ok(ApplyFile { 
+        vars: Option::None,
+        include: Option::None,
+        source_data: if source_data.is_empty() { Option::None }else{ Option::Some(source_data) }, 
+        tags: if tags.is_empty() { Option::None }else{ Option::Some(tags) }, 
+        topics: if topics.is_empty() { Option::None }else{ Option::Some(topics) }, 
+        authors: if authors.is_empty() { Option::None }else{ Option::Some(authors) },
+        setting: reduce_apply_setting(ret_settings)?
+    })
+}
+
+fn reduce_apply_setting(settings: Vec<Sourced<ApplyFileSetting>>) -> Result<Option<ApplyFileSetting>, Box<dyn Error>> {
+    let mut ret = ApplyFileSetting {
+        meta: Option::None,
+        query: Option::None,
+        import: Option::None,
+        file: Option::None,
+        find_similar: Option::None,
+        source_sites: Option::None
+    };
+    let mut sources: HashMap<&str, String> = HashMap::new();
+
+    for (source, setting) in settings {
+        if let Some(file) = setting.file {
+            if let Some(s) = sources.insert("file", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.file is declared in both {} and {}.", s, source))))
+            }
+            ret.file = Option::Some(file)
+        }
+        if let Some(query) = setting.query {
+            if let Some(s) = sources.insert("query", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.query is declared in both {} and {}.", s, source))))
+            }
+            ret.query = Option::Some(query)
+        }
+        if let Some(meta) = setting.meta {
+            if let Some(s) = sources.insert("meta", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.meta is declared in both {} and {}.", s, source))))
+            }
+            ret.meta = Option::Some(meta)
+        }
+        if let Some(import) = setting.import {
+            if let Some(s) = sources.insert("import", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.import is declared in both {} and {}.", s, source))))
+            }
+            ret.import = Option::Some(import)
+        }
+        if let Some(find_similar) = setting.find_similar {
+            if let Some(s) = sources.insert("find_similar", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.find_similar is declared in both {} and {}.", s, source))))
+            }
+            ret.find_similar = Option::Some(find_similar)
+        }
+        if let Some(sites) = setting.source_sites {
+            if let Some(s) = sources.insert("source_sites", source.clone()) {
+                return Result::Err(Box::new(ApplicationError::new(&format!("setting.source_sites is declared in both {} and {}.", s, source))))
+            }
+            ret.source_sites = Option::Some(sites)
+        }
+    }
+
+    Result::Ok(if ret.meta.is_some() || ret.query.is_some() || ret.import.is_some() || ret.file.is_some() || ret.find_similar.is_some() || ret.source_sites.is_some() { Option::Some(ret) }else{ Option::None })
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ApplyFile {
+    // vars在读取时已被展开并移除，这里声明它只是为了让JSON Schema与定位错误时的原始文本解析能接受它
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vars: Option<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    #[serde(alias = "source_data", alias = "sourceData", skip_serializing_if = "Option::is_none")]
+    pub source_data: Option<Vec<SourceDataBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<TagBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topics: Option<Vec<TopicBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authors: Option<Vec<AuthorBulkForm>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub setting: Option<ApplyFileSetting>
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ApplyFileSetting {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<MetaOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<QueryOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import: Option<ImportOptionUpdateForm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<StorageOptionUpdateForm>,
+    #[serde(alias = "find_similar", alias = "findSimilar", skip_serializing_if = "Option::is_none")]
+    pub find_similar: Option<FindSimilarOptionUpdateForm>,
+    #[serde(alias = "source_sites", alias = "sites", alias = "sourceSites", skip_serializing_if = "Option::is_none")]
+    pub source_sites: Option<Vec<SourceSiteUpdateForm>>
+}
\ No newline at end of file
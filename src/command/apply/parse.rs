@@ -0,0 +1,173 @@
+use std::{error::Error, fmt::{Display, Formatter}};
+use serde_json::Value;
+use super::{ApplyFile, template::{self, Vars}};
+
+// 带有位置信息的apply文件错误。line_column为1起始的行列号，path为错误在文档中的路径，如tags[3].children[1].mapping[0].site
+#[derive(Debug)]
+pub struct ApplyFileError {
+    pub file: String,
+    pub line_column: Option<(usize, usize)>,
+    pub path: Option<String>,
+    pub message: String
+}
+
+impl Display for ApplyFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some((line, column)) = self.line_column {
+            write!(f, ":{}:{}", line, column)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, ": {}", path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Error for ApplyFileError {}
+
+// 解析并展开一份apply文件文本。返回解析结果，以及供include的文件继承的变量
+pub fn parse_apply_file(file: &str, file_type: &str, text: &str, inherited: &Vars) -> Result<(ApplyFile, Vars), ApplyFileError> {
+    let document = parse_document(file, file_type, text)?;
+    let (document, vars) = match template::expand_document(document, inherited) {
+        Ok(r) => r,
+        Err(e) => return Result::Err(ApplyFileError { file: file.to_string(), line_column: Option::None, path: Option::None, message: e.to_string() })
+    };
+    match serde_path_to_error::deserialize::<_, ApplyFile>(document) {
+        Ok(apply_file) => Result::Ok((apply_file, vars)),
+        Err(e) => {
+            let path = e.path().to_string();
+            // 变量展开后文本中的位置已经丢失，因此从原始文本再解析一次以取得位置。只有在原始文本的错误也发生在同一路径时才采用它的位置
+            let line_column = locate_path(file_type, text, &path);
+            let path = if path == "." { Option::None }else{ Option::Some(path) };
+            Result::Err(ApplyFileError { file: file.to_string(), line_column, path, message: e.into_inner().to_string() })
+        }
+    }
+}
+
+// 推断stdin文本的文件类型。YAML能将几乎任何文本解析为字符串，因此只接受解析结果为map的情形
+pub fn detect_file_type(text: &str) -> &'static str {
+    if serde_json::from_str::<Value>(text).is_ok() {
+        "json"
+    }else if serde_yaml::from_str::<Value>(text).is_ok_and(|v| v.is_object()) {
+        "yaml"
+    }else{
+        "toml"
+    }
+}
+
+fn parse_document(file: &str, file_type: &str, text: &str) -> Result<Value, ApplyFileError> {
+    let (line_column, message) = match file_type {
+        "json" => match serde_json::from_str(text) {
+            Ok(v) => return Result::Ok(v),
+            Err(e) => (json_line_column(&e), strip_location(&e.to_string()))
+        },
+        "yaml" => match serde_yaml::from_str(text) {
+            Ok(v) => return Result::Ok(v),
+            Err(e) => (e.location().map(|l| (l.line(), l.column())), strip_location(&e.to_string()))
+        },
+        "toml" => match toml::from_str(text) {
+            Ok(v) => return Result::Ok(v),
+            Err(e) => (e.span().map(|s| offset_to_line_column(text, s.start)), e.message().to_string())
+        },
+        _ => (Option::None, format!("Unsupported file type {}.", file_type))
+    };
+    Result::Err(ApplyFileError { file: file.to_string(), line_column, path: Option::None, message })
+}
+
+fn locate_path(file_type: &str, text: &str, path: &str) -> Option<(usize, usize)> {
+    match file_type {
+        "json" => {
+            let mut deserializer = serde_json::Deserializer::from_str(text);
+            match serde_path_to_error::deserialize::<_, ApplyFile>(&mut deserializer) {
+                Err(e) if e.path().to_string() == path => json_line_column(e.inner()),
+                _ => Option::None
+            }
+        },
+        "yaml" => match serde_path_to_error::deserialize::<_, ApplyFile>(serde_yaml::Deserializer::from_str(text)) {
+            Err(e) if e.path().to_string() == path => e.inner().location().map(|l| (l.line(), l.column())),
+            _ => Option::None
+        },
+        "toml" => match serde_path_to_error::deserialize::<_, ApplyFile>(toml::Deserializer::new(text)) {
+            Err(e) if e.path().to_string() == path => e.inner().span().map(|s| offset_to_line_column(text, s.start)),
+            _ => Option::None
+        },
+        _ => Option::None
+    }
+}
+
+fn json_line_column(e: &serde_json::Error) -> Option<(usize, usize)> {
+    if e.line() == 0 { Option::None }else{ Option::Some((e.line(), e.column())) }
+}
+
+fn offset_to_line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+// 解析器的错误信息末尾自带位置，由ApplyFileError统一输出，因此去掉
+fn strip_location(message: &str) -> String {
+    match message.find(" at line ") {
+        Some(idx) => message[..idx].to_string(),
+        None => message.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_of(file_type: &str, text: &str) -> ApplyFileError {
+        parse_apply_file("f", file_type, text, &Vars::new()).err().unwrap()
+    }
+
+    #[test]
+    fn reports_nested_path_and_position() {
+        let e = error_of("yaml", "tags:\n  - name: a\n    children:\n      - name: b\n        colr: x\n");
+        assert_eq!(e.path.as_deref(), Option::Some("tags[0].children[0].colr"));
+        assert_eq!(e.line_column, Option::Some((5, 9)));
+
+        let e = error_of("json", "{\"tags\": [{\"name\": \"a\", \"colr\": 1}]}");
+        assert_eq!(e.path.as_deref(), Option::Some("tags[0].colr"));
+        assert_eq!(e.line_column, Option::Some((1, 30)));
+
+        let e = error_of("toml", "[[tags]]\nname = \"a\"\n\n[[tags]]\nname = 1\n");
+        assert_eq!(e.path.as_deref(), Option::Some("tags[1].name"));
+        assert_eq!(e.line_column, Option::Some((5, 8)));
+    }
+
+    #[test]
+    fn syntax_error_has_position_without_path() {
+        let e = error_of("json", "{\"tags\": [\n{\"name\": }]}");
+        assert_eq!(e.path, Option::None);
+        assert_eq!(e.line_column, Option::Some((2, 10)));
+        assert_eq!(e.to_string(), "f:2:10: expected value");
+    }
+
+    #[test]
+    fn position_is_dropped_when_error_comes_from_expansion() {
+        // 原始文本中同一路径上的错误仍可定位
+        let e = error_of("yaml", "vars:\n  c: x\ntags:\n  - name: a\n    colr: ${c}\n");
+        assert_eq!(e.line_column, Option::Some((5, 5)));
+        // 错误只在展开后出现时，原始文本中没有对应位置
+        let e = error_of("yaml", "vars:\n  n: 1\ntags:\n  - name: ${n}\n");
+        assert_eq!(e.path.as_deref(), Option::Some("tags[0].name"));
+        assert_eq!(e.line_column, Option::None);
+    }
+
+    #[test]
+    fn detects_file_type_of_stdin() {
+        assert_eq!(detect_file_type("{\"tags\": []}"), "json");
+        assert_eq!(detect_file_type("tags:\n  - name: a\n"), "yaml");
+        assert_eq!(detect_file_type("[[tags]]\nname = \"a\"\n"), "toml");
+    }
+
+    #[test]
+    fn converts_offset_to_line_column() {
+        assert_eq!(offset_to_line_column("ab\ncd", 0), (1, 1));
+        assert_eq!(offset_to_line_column("ab\ncd", 4), (2, 2));
+        assert_eq!(offset_to_line_column("ab", 10), (1, 3));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{error::Error, collections::HashSet, io::{stdin, stdout, Write}};
+use clap::ValueEnum;
+use crate::{module::{server::ServerManager, api::{bulk::{TagBulkForm, TopicBulkForm, AuthorBulkForm}, tag::{TagModule, TagTreeNode}, topic::TopicModule, author::AuthorModule}}, utils::error::ApplicationError};
+use super::ApplyFile;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PruneKind {
+    Tags,
+    Topics,
+    Authors
+}
+
+pub struct PruneItem {
+    pub kind: PruneKind,
+    pub id: i32,
+    pub target: String,
+    pub descendants: usize
+}
+
+pub async fn plan_prune(server_manager: &ServerManager, file: &ApplyFile, kinds: &[PruneKind]) -> Result<Vec<PruneItem>, Box<dyn Error>> {
+    let mut ret: Vec<PruneItem> = Vec::new();
+    if kinds.contains(&PruneKind::Tags) {
+        let tags = if let Some(tags) = &file.tags { tags }else{
+            return Result::Err(Box::new(ApplicationError::new("tags is not declared in apply files. Refuse to prune all tags.")))
+        };
+        let tree = TagModule::new(server_manager).tree().await?;
+        let declared: Vec<&TagBulkForm> = tags.iter().collect();
+        plan_tags(Option::None, &declared, &tree, &mut ret);
+    }
+    if kinds.contains(&PruneKind::Topics) {
+        let topics = if let Some(topics) = &file.topics { topics }else{
+            return Result::Err(Box::new(ApplicationError::new("topics is not declared in apply files. Refuse to prune all topics.")))
+        };
+        let mut declared: HashSet<&str> = HashSet::new();
+        for topic in topics {
+            collect_topic_names(topic, &mut declared);
+        }
+        for topic in TopicModule::new(server_manager).list_all().await? {
+            if !declared.contains(topic.name.as_str()) {
+                ret.push(PruneItem { kind: PruneKind::Topics, id: topic.id, target: format!("topic '{}'", topic.name), descendants: 0 });
+            }
+        }
+    }
+    if kinds.contains(&PruneKind::Authors) {
+        let authors = if let Some(authors) = &file.authors { authors }else{
+            return Result::Err(Box::new(ApplicationError::new("authors is not declared in apply files. Refuse to prune all authors.")))
+        };
+        let declared = collect_author_names(authors);
+        for author in AuthorModule::new(server_manager).list_all().await? {
+            if !declared.contains(author.name.as_str()) {
+                ret.push(PruneItem { kind: PruneKind::Authors, id: author.id, target: format!("author '{}'", author.name), descendants: 0 });
+            }
+        }
+    }
+    Result::Ok(ret)
+}
+
+pub fn print_prune(items: &[PruneItem]) {
+    for item in items {
+        print_prune_target(&item.target, item.descendants);
+    }
+}
+
+pub fn print_prune_target(target: &str, descendants: usize) {
+    if descendants > 0 {
+        println!("\x1b[1;31m- {}\x1b[0m (with {} descendant(s))", target, descendants);
+    }else{
+        println!("\x1b[1;31m- {}\x1b[0m", target);
+    }
+}
+
+pub fn confirm_prune(items: &[PruneItem]) -> bool {
+    print!("{} item(s) above are not declared in apply files and will be deleted. Continue? [y/N] ", items.len());
+    if stdout().flush().is_err() {
+        return false
+    }
+    let mut line = String::new();
+    if stdin().read_line(&mut line).is_err() {
+        return false
+    }
+    let answer = line.trim().to_lowercase();
+    answer == "y" || answer == "yes"
+}
+
+// 逐项删除，返回每一项的错误信息(成功时为None)
+pub async fn execute_prune(server_manager: &ServerManager, items: &[PruneItem]) -> Vec<Option<String>> {
+    let mut tag_module = TagModule::new(server_manager);
+    let mut topic_module = TopicModule::new(server_manager);
+    let mut author_module = AuthorModule::new(server_manager);
+
+    let mut ret = Vec::with_capacity(items.len());
+    for item in items {
+        let result = match item.kind {
+            PruneKind::Tags => tag_module.delete(item.id).await,
+            PruneKind::Topics => topic_module.delete(item.id).await,
+            PruneKind::Authors => author_module.delete(item.id).await
+        };
+        ret.push(result.err().map(|e| e.to_string()));
+    }
+    ret
+}
+
+// 在同一层级中按name或rename匹配，未声明的节点连同其子树一起删除，因此不再向下递归。
+// rename已经应用过的实体在server上使用新名称，因此rename的目标也视为已声明。
+fn plan_tags(parent: Option<&str>, declared: &[&TagBulkForm], current: &[TagTreeNode], ret: &mut Vec<PruneItem>) {
+    for node in current {
+        let address = if let Some(parent) = parent { format!("{}.{}", parent, node.name) }else{ node.name.clone() };
+        if let Some(tag) = declared.iter().find(|t| t.name == node.name || t.rename.as_deref() == Some(node.name.as_str())) {
+            if let Some(children) = &node.children {
+                let declared_children: Vec<&TagBulkForm> = tag.children.iter().flat_map(|c| c.iter()).map(|c| c.as_ref()).collect();
+                plan_tags(Option::Some(&address), &declared_children, children, ret);
+            }
+        }else{
+            ret.push(PruneItem { kind: PruneKind::Tags, id: node.id, target: format!("tag '{}'", address), descendants: count_descendants(node) });
+        }
+    }
+}
+
+fn count_descendants(node: &TagTreeNode) -> usize {
+    node.children.as_ref().map(|c| c.iter().map(|n| 1 + count_descendants(n)).sum()).unwrap_or(0)
+}
+
+fn collect_topic_names<'t>(topic: &'t TopicBulkForm, ret: &mut HashSet<&'t str>) {
+    ret.insert(topic.name.as_str());
+    if let Some(rename) = &topic.rename {
+        ret.insert(rename.as_str());
+    }
+    if let Some(children) = &topic.children {
+        for child in children {
+            collect_topic_names(child, ret);
+        }
+    }
+}
+
+fn collect_author_names(authors: &[AuthorBulkForm]) -> HashSet<&str> {
+    authors.iter().flat_map(|a| std::iter::once(a.name.as_str()).chain(a.rename.as_deref())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn targets(items: &[PruneItem]) -> Vec<&str> {
+        items.iter().map(|i| i.target.as_str()).collect()
+    }
+
+    #[test]
+    fn plan_tags_keeps_renamed_tags() {
+        let tags: Vec<TagBulkForm> = serde_json::from_value(json!([
+            { "name": "old", "rename": "new", "children": [{ "name": "child-old", "rename": "child-new" }] },
+            { "name": "kept" }
+        ])).unwrap();
+        // rename已应用后server上的状态
+        let tree: Vec<TagTreeNode> = serde_json::from_value(json!([
+            { "id": 1, "name": "new", "children": [{ "id": 2, "name": "child-new", "children": null }, { "id": 3, "name": "extra", "children": null }] },
+            { "id": 4, "name": "kept", "children": null },
+            { "id": 5, "name": "undeclared", "children": [{ "id": 6, "name": "leaf", "children": null }] }
+        ])).unwrap();
+        let declared: Vec<&TagBulkForm> = tags.iter().collect();
+        let mut ret = Vec::new();
+        plan_tags(Option::None, &declared, &tree, &mut ret);
+        assert_eq!(targets(&ret), vec!["tag 'new.extra'", "tag 'undeclared'"]);
+        assert_eq!(ret[1].descendants, 1);
+    }
+
+    #[test]
+    fn collect_topic_names_includes_rename() {
+        let topics: Vec<TopicBulkForm> = serde_json::from_value(json!([
+            { "name": "old", "rename": "new", "children": [{ "name": "child", "rename": "child-new" }] }
+        ])).unwrap();
+        let mut declared = HashSet::new();
+        for topic in &topics {
+            collect_topic_names(topic, &mut declared);
+        }
+        assert_eq!(declared, HashSet::from(["old", "new", "child", "child-new"]));
+    }
+
+    #[test]
+    fn collect_author_names_includes_rename() {
+        let authors: Vec<AuthorBulkForm> = serde_json::from_value(json!([
+            { "name": "old", "rename": "new" },
+            { "name": "plain" }
+        ])).unwrap();
+        assert_eq!(collect_author_names(&authors), HashSet::from(["old", "new", "plain"]));
+    }
+}
@@ -0,0 +1,164 @@
+use serde_json::{Map, Value};
+use crate::utils::error::ApplicationError;
+
+pub type Vars = Map<String, Value>;
+
+// 解析命令行中以NAME=VALUE形式给出的变量
+pub fn parse_vars(items: &[String]) -> Result<Vars, ApplicationError> {
+    let mut ret = Vars::new();
+    for item in items {
+        match item.split_once('=') {
+            Some((name, value)) if !name.is_empty() => {
+                ret.insert(name.to_string(), Value::String(value.to_string()));
+            },
+            _ => return Result::Err(ApplicationError::new(&format!("Variable '{}' should be in NAME=VALUE form.", item)))
+        }
+    }
+    Result::Ok(ret)
+}
+
+// 取出文档的vars部分并展开文档。文档声明的变量只作为默认值，命令行或include方传入的同名变量优先。
+// 只有声明了vars部分的文档(可以是空的vars: {})才会展开，其他文档按字面读取，因此已有的含"${"的值不受影响。
+// 返回展开后的文档，以及供include的文件继承的变量
+pub fn expand_document(mut document: Value, inherited: &Vars) -> Result<(Value, Vars), ApplicationError> {
+    let mut vars = inherited.clone();
+    let declared = if let Value::Object(map) = &mut document { map.remove("vars") }else{ Option::None };
+    match declared {
+        Some(Value::Object(declared)) => for (name, value) in declared {
+            if !vars.contains_key(&name) {
+                let value = expand_value(value, inherited)?;
+                vars.insert(name, value);
+            }
+        },
+        Some(Value::Null) => {},
+        Some(_) => return Result::Err(ApplicationError::new("vars should be a map.")),
+        None => return Result::Ok((document, vars))
+    }
+    let document = expand_value(document, &vars)?;
+    Result::Ok((document, vars))
+}
+
+fn expand_value(value: Value, vars: &Vars) -> Result<Value, ApplicationError> {
+    match value {
+        Value::String(s) => expand_string(&s, vars),
+        Value::Array(arr) => {
+            let mut ret: Vec<Value> = Vec::with_capacity(arr.len());
+            for item in arr {
+                // 数组中形如"...${name}"的元素会被展开为变量数组中的全部元素。"...$${name}"是转义，得到字面量"...${name}"
+                if let Some(name) = item.as_str().and_then(|s| s.strip_prefix("...")).and_then(whole_var_name) {
+                    match vars.get(name) {
+                        Some(Value::Array(a)) => ret.extend(a.iter().cloned()),
+                        Some(_) => return Result::Err(ApplicationError::new(&format!("Variable '{}' cannot be spread because it is not an array.", name))),
+                        None => return Result::Err(ApplicationError::new(&format!("Variable '{}' is not defined.", name)))
+                    }
+                }else{
+                    ret.push(expand_value(item, vars)?);
+                }
+            }
+            Result::Ok(Value::Array(ret))
+        },
+        Value::Object(map) => {
+            let mut ret = Map::new();
+            for (k, v) in map {
+                ret.insert(k, expand_value(v, vars)?);
+            }
+            Result::Ok(Value::Object(ret))
+        },
+        other => Result::Ok(other)
+    }
+}
+
+fn expand_string(s: &str, vars: &Vars) -> Result<Value, ApplicationError> {
+    // 整个字符串恰好是一个变量引用时，替换为变量的原始值，因此变量可以是数组或对象
+    if let Some(name) = whole_var_name(s) {
+        return match vars.get(name) {
+            Some(v) => Result::Ok(v.clone()),
+            None => Result::Err(ApplicationError::new(&format!("Variable '{}' is not defined.", name)))
+        }
+    }
+    if !s.contains("${") {
+        return Result::Ok(Value::String(s.to_string()))
+    }
+
+    let mut ret = String::new();
+    let mut rest = s;
+    while let Some(idx) = rest.find("${") {
+        if idx > 0 && rest[..idx].ends_with('$') {
+            // "$${"是转义，输出字面量"${"
+            ret.push_str(&rest[..idx - 1]);
+            ret.push_str("${");
+            rest = &rest[idx + 2..];
+            continue
+        }
+        ret.push_str(&rest[..idx]);
+        let end = if let Some(end) = rest[idx..].find('}') { idx + end }else{
+            return Result::Err(ApplicationError::new(&format!("Unclosed variable reference in '{}'.", s)))
+        };
+        let name = &rest[idx + 2..end];
+        match vars.get(name) {
+            Some(Value::String(v)) => ret.push_str(v),
+            Some(Value::Number(v)) => ret.push_str(&v.to_string()),
+            Some(Value::Bool(v)) => ret.push_str(&v.to_string()),
+            Some(_) => return Result::Err(ApplicationError::new(&format!("Variable '{}' cannot be interpolated into a string because it is not a scalar.", name))),
+            None => return Result::Err(ApplicationError::new(&format!("Variable '{}' is not defined.", name)))
+        }
+        rest = &rest[end + 1..];
+    }
+    ret.push_str(rest);
+    Result::Ok(Value::String(ret))
+}
+
+fn whole_var_name(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("${")?.strip_suffix('}')?;
+    if name.is_empty() || name.contains(['{', '}', '$']) { Option::None }else{ Option::Some(name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn vars(value: Value) -> Vars {
+        if let Value::Object(map) = value { map }else{ panic!("vars should be a map") }
+    }
+
+    #[test]
+    fn document_without_vars_is_read_literally() {
+        let document = json!({"setting": {"import": {"pattern": "${name}.jpg"}}});
+        let (expanded, _) = expand_document(document.clone(), &vars(json!({"name": "x"}))).unwrap();
+        assert_eq!(expanded, document);
+    }
+
+    #[test]
+    fn expands_declared_and_inherited_vars() {
+        let document = json!({"vars": {"prefix": "a", "tags": ["t1", "t2"]}, "name": "${prefix}-${suffix}", "list": ["t0", "...${tags}"], "raw": "${tags}"});
+        let (expanded, inherited) = expand_document(document, &vars(json!({"prefix": "cli", "suffix": 1}))).unwrap();
+        assert_eq!(expanded, json!({"name": "cli-1", "list": ["t0", "t1", "t2"], "raw": ["t1", "t2"]}));
+        assert_eq!(inherited.get("prefix"), Option::Some(&json!("cli")));
+        assert_eq!(inherited.get("tags"), Option::Some(&json!(["t1", "t2"])));
+    }
+
+    #[test]
+    fn escapes_produce_literals() {
+        let document = json!({"vars": {}, "a": "$${x}", "b": "p$${x}q", "list": ["...$${x}"]});
+        let (expanded, _) = expand_document(document, &Vars::new()).unwrap();
+        assert_eq!(expanded, json!({"a": "${x}", "b": "p${x}q", "list": ["...${x}"]}));
+    }
+
+    #[test]
+    fn reports_undefined_and_invalid_references() {
+        assert!(expand_document(json!({"vars": {}, "a": "${x}"}), &Vars::new()).is_err());
+        assert!(expand_document(json!({"vars": {}, "a": "p${x"}), &Vars::new()).is_err());
+        assert!(expand_document(json!({"vars": {"x": "s"}, "a": ["...${x}"]}), &Vars::new()).is_err());
+        assert!(expand_document(json!({"vars": {"x": [1]}, "a": "p${x}"}), &Vars::new()).is_err());
+        assert!(expand_document(json!({"vars": 1}), &Vars::new()).is_err());
+    }
+
+    #[test]
+    fn parses_vars_from_command_line() {
+        let parsed = parse_vars(&["a=1".to_string(), "b=x=y".to_string()]).unwrap();
+        assert_eq!(Value::Object(parsed), json!({"a": "1", "b": "x=y"}));
+        assert!(parse_vars(&["=1".to_string()]).is_err());
+        assert!(parse_vars(&["a".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,79 @@
+use glob::Pattern;
+use serde::Serialize;
+use crate::{command::{CommandError, CommandResult}, utils::output::Output};
+use super::{ApplyFile, ApplyInputType, ApplyReader, reduce_apply_files, template, merge::MergeStrategy, parse::ApplyFileError};
+
+#[derive(Serialize)]
+struct ValidateError {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>
+}
+
+#[derive(Serialize)]
+struct ValidateSummary {
+    files: usize,
+    errors: usize
+}
+
+pub fn validate(output: &Output, input: &Vec<ApplyInputType>, globs: &[Pattern], vars: &[String], merge: MergeStrategy) -> CommandResult {
+    let vars = match template::parse_vars(vars) {
+        Ok(v) => v,
+        Err(e) => {
+            output.error(&e.to_string(), "");
+            return Result::Err(CommandError::Input)
+        }
+    };
+
+    let mut reader = ApplyReader::new(globs, true);
+    if let Err(e) = reader.read(input, &vars) {
+        output.error("Apply input read error.", e);
+        return Result::Err(CommandError::Input)
+    }
+    let mut errors = reader.errors.take().unwrap_or_default();
+    let files = reader.files;
+    let count = files.len();
+
+    // 单个文件都有效时，再检查合并文件时的冲突
+    if errors.is_empty() && count > 0 {
+        if let Err(e) = reduce_apply_files(files, merge) {
+            errors.push(e);
+        }
+    }
+
+    let items: Vec<ValidateError> = errors.iter().map(|e| match e.downcast_ref::<ApplyFileError>() {
+        Some(f) => ValidateError { message: e.to_string(), file: Option::Some(f.file.clone()), line: f.line_column.map(|(l, _)| l), column: f.line_column.map(|(_, c)| c), path: f.path.clone() },
+        None => ValidateError { message: e.to_string(), file: Option::None, line: Option::None, column: Option::None, path: Option::None }
+    }).collect();
+    output.list(&items, &ValidateSummary { files: count, errors: items.len() }, |items, summary| {
+        for e in items {
+            println!("\x1b[1;31merror\x1b[0m: {}", e.message);
+        }
+        if summary.files == 0 && items.is_empty() {
+            println!("apply files is empty.");
+        }else{
+            println!("Validate: {} file(s) valid, {} error(s).", summary.files, summary.errors);
+        }
+    });
+    if items.is_empty() { Result::Ok(()) }else{ Result::Err(CommandError::Input) }
+}
+
+pub fn print_schema() -> CommandResult {
+    let schema = schemars::schema_for!(ApplyFile);
+    match serde_json::to_string_pretty(&schema) {
+        Ok(text) => {
+            println!("{}", text);
+            Result::Ok(())
+        },
+        Err(e) => {
+            eprintln!("Generate JSON Schema failed. {}", e);
+            Result::Err(CommandError::Failed)
+        }
+    }
+}
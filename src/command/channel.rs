@@ -1,18 +1,374 @@
+use std::io::{stdin, stdout, stderr, IsTerminal, Write};
+use serde::Serialize;
+use crate::module::server::ServerStatusType;
+use crate::utils::error::HedgeError;
+use super::{Context, CommandError, CommandResult};
 
-use super::Context;
+#[derive(Serialize)]
+struct ChannelInfoResult<'t> {
+    using_channel: &'t str,
+    channels: Vec<String>
+}
+
+#[derive(Serialize)]
+struct ChannelListResult<'t> {
+    using_channel: &'t str,
+    channels: Vec<ChannelDetailResult>
+}
+
+#[derive(Serialize)]
+struct ChannelDetailResult {
+    name: String,
+    using: bool,
+    mode: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    pid: Option<i64>,
+    host: Option<String>,
+    port: Option<i32>,
+    last_start_time: Option<i64>,
+    log_size: Option<u64>,
+    disk_size: u64
+}
+
+pub async fn info(context: &Context<'_>) -> CommandResult {
+    let channels = match context.channel_manager.list_channel() {
+        Ok(c) => c,
+        Err(e) => {
+            context.output.failure("Cannot list channels.", &e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+    let mut details = Vec::with_capacity(channels.len());
+    for channel in &channels {
+        match channel_detail(context, channel).await {
+            Ok(d) => details.push(d),
+            Err(e) => {
+                context.output.failure(&format!("Cannot read channel {}.", channel), &e);
+                return Result::Err(CommandError::Failed)
+            }
+        }
+    }
+    let result = ChannelListResult { using_channel: context.channel_manager.current_channel(), channels: details };
+    context.output.result(&result, |r| {
+        println!("Using channel: {}", r.using_channel);
+        println!("---");
+        println!("  {:<20} {:<7} {:<16} {:<8} {:<6} {:<19} {:>9} {:>9}", "NAME", "MODE", "STATUS", "PID", "PORT", "LAST START", "LOG", "SIZE");
+        for c in &r.channels {
+            println!("{} {:<20} {:<7} {:<16} {:<8} {:<6} {:<19} {:>9} {:>9}",
+                if c.using { "*" }else{ " " },
+                c.name,
+                c.mode,
+                c.status,
+                c.pid.map(|p| p.to_string()).unwrap_or("-".to_string()),
+                c.port.map(|p| p.to_string()).unwrap_or("-".to_string()),
+                c.last_start_time.map(format_time).unwrap_or("-".to_string()),
+                c.log_size.map(human_size).unwrap_or("-".to_string()),
+                human_size(c.disk_size)
+            )
+        }
+    });
+    Result::Ok(())
+}
+
+pub async fn show(context: &Context<'_>, channel_name: &str) -> CommandResult {
+    let detail = match channel_detail(context, channel_name).await {
+        Ok(d) => d,
+        Err(e) => {
+            context.output.failure(&format!("Cannot read channel {}.", channel_name), &e);
+            return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+        }
+    };
+    context.output.result(&detail, |d| {
+        println!("Channel: {}{}", d.name, if d.using { " (using)" }else{ "" });
+        println!("Mode: {}", d.mode);
+        println!("Status: {}", d.status);
+        if let Some(error) = &d.error {
+            println!("Error: {}", error)
+        }
+        if let Some(pid) = d.pid {
+            println!("PID: {}", pid)
+        }
+        if let Some(host) = &d.host {
+            println!("Host: {}", host)
+        }
+        if let Some(port) = d.port {
+            println!("Port: {}", port)
+        }
+        if let Some(t) = d.last_start_time {
+            println!("Last Start: {}", format_time(t))
+        }
+        if let Some(size) = d.log_size {
+            println!("Log Size: {}", human_size(size))
+        }
+        println!("Disk Size: {}", human_size(d.disk_size));
+    });
+    Result::Ok(())
+}
+
+// 读取channel的目录占用与server状态。server状态读取失败时不中断，记录在error中
+async fn channel_detail(context: &Context<'_>, channel: &str) -> Result<ChannelDetailResult, HedgeError> {
+    let usage = context.channel_manager.channel_usage(channel)?;
+    let mut server_manager = context.server_manager.with_channel(channel);
+    let (mode, status, error, pid, host, port, start_time) = match server_manager.status().await {
+        Ok(s) => {
+            let mode = if s.status == ServerStatusType::NotInitialized && !s.remote_mode { "none" }else if s.remote_mode { "remote" }else{ "local" };
+            (mode.to_string(), s.status.to_string(), Option::None, s.pid, s.host, s.port, s.start_time)
+        },
+        Err(e) => ("unknown".to_string(), "Error".to_string(), Option::Some(e.to_string()), Option::None, Option::None, Option::None, Option::None)
+    };
+    Result::Ok(ChannelDetailResult {
+        name: channel.to_string(),
+        using: channel == context.channel_manager.current_channel(),
+        mode,
+        status,
+        error,
+        pid,
+        host,
+        port,
+        last_start_time: start_time.or(usage.log_created),
+        log_size: usage.log_size,
+        disk_size: usage.disk_size
+    })
+}
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(timestamp).map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or("-".to_string())
+}
+
+fn human_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} {}", size, units[0]) }else{ format!("{:.1} {}", value, units[unit]) }
+}
+
+pub fn use_channel(context: &Context, channel_name: String) -> CommandResult {
+    if let Err(e) = context.channel_manager.use_channel(&channel_name) {
+        context.output.failure("Cannot switch channel.", &e);
+        return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+    }
+    let channels = match context.channel_manager.list_channel() {
+        Ok(c) => c,
+        Err(e) => {
+            context.output.failure("Cannot list channels.", &e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+    let result = ChannelInfoResult { using_channel: &channel_name, channels };
+    context.output.result(&result, |r| println!("Using channel: {}", r.using_channel));
+    Result::Ok(())
+}
+
+#[derive(Serialize)]
+struct ChannelChangeResult<'t> {
+    action: &'static str,
+    channel: &'t str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_name: Option<&'t str>
+}
+
+pub fn create(context: &Context, channel_name: &str) -> CommandResult {
+    if let Err(e) = context.channel_manager.create_channel(channel_name) {
+        context.output.failure("Cannot create channel.", &e);
+        return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+    }
+    let result = ChannelChangeResult { action: "create", channel: channel_name, new_name: Option::None };
+    context.output.result(&result, |r| println!("Channel {} created.", r.channel));
+    Result::Ok(())
+}
+
+pub fn remove(context: &Context, channel_name: &str, yes: bool) -> CommandResult {
+    if !yes {
+        if !context.output.is_text() {
+            context.output.error("Removing channel cannot ask for confirmation when output is not text. Use --yes to confirm it in advance.", "");
+            return Result::Err(CommandError::Input)
+        }
+        if !confirm(&format!("Channel {} and all its data will be deleted. Continue? [y/N] ", channel_name)) {
+            println!("Remove is cancelled.");
+            return Result::Ok(())
+        }
+    }
+    if let Err(e) = context.channel_manager.remove_channel(channel_name) {
+        context.output.failure("Cannot remove channel.", &e);
+        return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+    }
+    let result = ChannelChangeResult { action: "remove", channel: channel_name, new_name: Option::None };
+    context.output.result(&result, |r| println!("Channel {} removed.", r.channel));
+    Result::Ok(())
+}
+
+pub fn rename(context: &Context, channel_name: &str, new_name: &str) -> CommandResult {
+    if let Err(e) = context.channel_manager.rename_channel(channel_name, new_name) {
+        context.output.failure("Cannot rename channel.", &e);
+        return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+    }
+    let result = ChannelChangeResult { action: "rename", channel: channel_name, new_name: Option::Some(new_name) };
+    context.output.result(&result, |r| println!("Channel {} renamed to {}.", r.channel, new_name));
+    Result::Ok(())
+}
+
+pub fn copy(context: &Context, channel_name: &str, new_name: &str) -> CommandResult {
+    if let Err(e) = context.channel_manager.copy_channel(channel_name, new_name) {
+        context.output.failure("Cannot copy channel.", &e);
+        return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+    }
+    let result = ChannelChangeResult { action: "copy", channel: channel_name, new_name: Option::Some(new_name) };
+    context.output.result(&result, |r| println!("Channel {} copied to {}.", r.channel, new_name));
+    Result::Ok(())
+}
+
+#[derive(Serialize)]
+struct LoginResult<'t> {
+    channel: &'t str,
+    host: &'t str,
+    token_source: &'static str
+}
+
+#[derive(Serialize)]
+struct WhoamiResult {
+    channel: String,
+    mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_source: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connected: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    authenticated: Option<bool>
+}
+
+pub async fn login(context: &mut Context<'_>, host: &str, token: Option<String>, credentials_file: bool) -> CommandResult {
+    let token = match token {
+        Some(t) => t,
+        None => match read_token() {
+            Some(t) => t,
+            None => {
+                context.output.error("Token is required.", "Use --token, or input it from stdin.");
+                return Result::Err(CommandError::Input)
+            }
+        }
+    };
+    // 先确认host可以连接、token可以通过鉴权，再写入
+    context.server_manager.set_access_remote(host.to_string(), token.clone());
+    if let Err(e) = context.server_manager.check_health().await {
+        context.output.error(&format!("Cannot connect to {}.", host), &e);
+        return Result::Err(CommandError::of_request(e.as_ref()))
+    }
+    if let Err(e) = context.server_manager.check_auth().await {
+        context.output.error("Token is rejected by server.", &e);
+        return Result::Err(CommandError::of_request(e.as_ref()))
+    }
+    if let Err(e) = context.server_manager.save_login(host, &token, credentials_file) {
+        context.output.failure("Cannot save login.", &e);
+        return Result::Err(CommandError::Failed)
+    }
+    let result = LoginResult { channel: context.channel_manager.current_channel(), host, token_source: if credentials_file { "credentials" }else{ "client.dat" } };
+    context.output.result(&result, |r| println!("Logged in to {} with channel {}.", r.host, r.channel));
+    Result::Ok(())
+}
+
+pub fn logout(context: &Context) -> CommandResult {
+    match context.server_manager.logout() {
+        Err(e) => {
+            context.output.failure("Cannot log out.", &e);
+            Result::Err(CommandError::Failed)
+        },
+        Ok(removed) => {
+            let message = if removed { "Logged out." }else{ "Not logged in." };
+            context.output.result(&serde_json::json!({ "channel": context.channel_manager.current_channel(), "logged_out": removed }), |_| println!("{}", message));
+            Result::Ok(())
+        }
+    }
+}
+
+pub async fn whoami(context: &mut Context<'_>) -> CommandResult {
+    let info = match context.server_manager.login_info() {
+        Ok(i) => i,
+        Err(e) => {
+            context.output.failure("Cannot read login info.", &e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+    let mut result = WhoamiResult { channel: info.channel, mode: info.mode, host: info.host, token: info.token.as_deref().map(redact), token_source: info.token_source, connected: Option::None, authenticated: Option::None };
+    // 只有远程模式需要检查登录状态
+    if let (true, Some(host), Some(token)) = (result.mode == "remote", &result.host, info.token) {
+        context.server_manager.set_access_remote(host.clone(), token);
+        let connected = context.server_manager.check_health().await.is_ok();
+        result.connected = Option::Some(connected);
+        result.authenticated = Option::Some(connected && context.server_manager.check_auth().await.is_ok());
+    }
+    context.output.result(&result, |r| {
+        println!("Channel: {}", r.channel);
+        println!("Mode: {}", r.mode);
+        if let Some(host) = &r.host {
+            println!("Host: {}", host)
+        }
+        match (&r.token, r.token_source) {
+            (Some(token), Some(source)) => println!("Token: {} (from {})", token, source),
+            _ => if r.mode == "remote" { println!("Token: \x1b[1;33mnot logged in\x1b[0m") }
+        }
+        if let Some(connected) = r.connected {
+            println!("Connected: {}", if connected { "\x1b[1;32myes\x1b[0m" }else{ "\x1b[1;31mno\x1b[0m" })
+        }
+        if let Some(authenticated) = r.authenticated {
+            println!("Authenticated: {}", if authenticated { "\x1b[1;32myes\x1b[0m" }else{ "\x1b[1;31mno\x1b[0m" })
+        }
+    });
+    Result::Ok(())
+}
 
-pub fn info(context: &Context) {
-    let list = context.channel_manager.list_channel();
-    let current = context.channel_manager.current_channel();
-    println!("Using channel: {}", current);
-    println!("---");
-    println!("Channel list:");
-    for c in list {
-        println!("- {}", c)
+fn confirm(prompt: &str) -> bool {
+    print!("{}", prompt);
+    if stdout().flush().is_err() {
+        return false
+    }
+    let mut line = String::new();
+    if stdin().read_line(&mut line).is_err() {
+        return false
     }
+    let answer = line.trim().to_lowercase();
+    answer == "y" || answer == "yes"
 }
 
-pub fn use_channel(context: &Context, channel_name: String) {
-    context.channel_manager.use_channel(&channel_name);
-    println!("Using channel: {}", &channel_name);
-}
\ No newline at end of file
+// 从stdin读取token。终端中会先给出提示
+fn read_token() -> Option<String> {
+    if stdin().is_terminal() {
+        eprint!("Token: ");
+        let _ = stderr().flush();
+    }
+    let mut line = String::new();
+    stdin().read_line(&mut line).ok()?;
+    Option::Some(line.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+// 只显示token的头尾
+fn redact(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 8 {
+        "***".to_string()
+    }else{
+        format!("{}***{}", chars[..4].iter().collect::<String>(), chars[chars.len() - 4..].iter().collect::<String>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_shows_only_ends_of_token() {
+        assert_eq!(redact("goodtoken12345"), "good***2345");
+        assert_eq!(redact("令牌一二三四五六七八"), "令牌一二***五六七八");
+        assert_eq!(redact("12345678"), "***");
+        assert_eq!(redact(""), "***");
+    }
+}
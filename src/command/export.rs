@@ -0,0 +1,214 @@
+use std::{path::PathBuf, error::Error, collections::{HashMap, HashSet}, future::Future};
+use futures_util::{StreamExt, stream};
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::{module::{server::ServerManager, api::{bulk::{TagBulkForm, TopicBulkForm, AuthorBulkForm}, setting::SettingModule, tag::{TagModule, TagTreeNode, TagDetailRes}, topic::{TopicModule, TopicDetailRes}, author::AuthorModule}}, utils::error::ApplicationError};
+use super::{Context, CommandError, CommandResult, apply::{ApplyFile, ApplyFileSetting}};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Yaml,
+    Toml
+}
+
+pub struct ExportParts {
+    pub tags: bool,
+    pub topics: bool,
+    pub authors: bool,
+    pub setting: bool
+}
+
+#[derive(Serialize)]
+struct ExportResult {
+    file: String,
+    tags: usize,
+    topics: usize,
+    authors: usize,
+    setting: bool
+}
+
+pub async fn export(context: &mut Context<'_>, file: &Option<PathBuf>, format: Option<ExportFormat>, parts: &ExportParts) -> CommandResult {
+    // 输出到stdout且未指定格式时，json/ndjson输出格式下导出json
+    let format = if format.is_none() && file.is_none() && !context.output.is_text() { Option::Some(ExportFormat::Json) }else{ format };
+    let format = match get_export_format(file, format) {
+        Ok(f) => f,
+        Err(e) => {
+            context.output.error(&e.to_string(), "");
+            return Result::Err(CommandError::Input)
+        }
+    };
+
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
+    }
+
+    let apply_file = match fetch_apply_file(context.server_manager, parts).await {
+        Ok(f) => f,
+        Err(e) => {
+            context.output.error("Error occrred in requesting.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
+        }
+    };
+
+    let text = match write_apply_file(&apply_file, format) {
+        Ok(t) => t,
+        Err(e) => {
+            context.output.error("Export file format error.", e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+
+    if let Some(file) = file {
+        if let Err(e) = std::fs::write(file, text) {
+            context.output.error(&format!("Cannot write export file {}.", file.to_string_lossy()), e);
+            return Result::Err(CommandError::Failed)
+        }
+        let result = ExportResult {
+            file: file.to_string_lossy().to_string(),
+            tags: apply_file.tags.as_ref().map(|f| f.iter().map(count_tag).sum()).unwrap_or(0),
+            topics: apply_file.topics.as_ref().map(|f| f.iter().map(count_topic).sum()).unwrap_or(0),
+            authors: apply_file.authors.as_ref().map(|f| f.len()).unwrap_or(0),
+            setting: apply_file.setting.is_some()
+        };
+        context.output.result(&result, |r| println!("Export {} tag(s), {} topic(s), {} author(s){} to {}.", r.tags, r.topics, r.authors, if r.setting { " and setting" }else{ "" }, r.file));
+    }else{
+        print!("{}", text);
+    }
+    Result::Ok(())
+}
+
+pub async fn fetch_apply_file(server_manager: &ServerManager, parts: &ExportParts) -> Result<ApplyFile, Box<dyn Error>> {
+    Result::Ok(ApplyFile {
+        vars: Option::None,
+        include: Option::None,
+        source_data: Option::None,
+        tags: if parts.tags { Option::Some(fetch_tags(server_manager).await?) }else{ Option::None },
+        topics: if parts.topics { Option::Some(fetch_topics(server_manager).await?) }else{ Option::None },
+        authors: if parts.authors { Option::Some(fetch_authors(server_manager).await?) }else{ Option::None },
+        setting: if parts.setting { Option::Some(fetch_setting(server_manager).await?) }else{ Option::None }
+    })
+}
+
+// 同时进行的详情请求数
+const DETAIL_CONCURRENCY: usize = 16;
+
+// 分批并发获取每一项的详情，结果保持原顺序
+pub async fn fetch_details<'a, T, R, F, Fut>(items: &'a [T], fetch: F) -> Result<Vec<R>, Box<dyn Error>> where F: Fn(&'a T) -> Fut, Fut: Future<Output = Result<R, String>> {
+    let results: Vec<Result<R, String>> = stream::iter(items).map(fetch).buffered(DETAIL_CONCURRENCY).collect().await;
+    results.into_iter().map(|r| r.map_err(|e| Box::new(ApplicationError::new(&e)) as Box<dyn Error>)).collect()
+}
+
+async fn fetch_tags(server_manager: &ServerManager) -> Result<Vec<TagBulkForm>, Box<dyn Error>> {
+    let mut tag_module = TagModule::new(server_manager);
+    let tree = tag_module.tree().await?;
+
+    let mut nodes: Vec<&TagTreeNode> = Vec::new();
+    let mut stack: Vec<&TagTreeNode> = tree.iter().collect();
+    while let Some(node) = stack.pop() {
+        nodes.push(node);
+        if let Some(children) = &node.children {
+            stack.extend(children.iter());
+        }
+    }
+    let tag_module = &tag_module;
+    let details = fetch_details(&nodes, |node| async move {
+        tag_module.get(node.id).await.map(|d| (node.id, d)).map_err(|e| format!("Get tag '{}' failed. {}", node.name, e))
+    }).await?;
+    let details: HashMap<i32, TagDetailRes> = details.into_iter().collect();
+
+    Result::Ok(tree.iter().map(|node| build_tag_form(node, &details)).collect())
+}
+
+fn build_tag_form(node: &TagTreeNode, details: &HashMap<i32, TagDetailRes>) -> TagBulkForm {
+    let children = node.children.as_ref()
+        .filter(|c| !c.is_empty())
+        .map(|c| c.iter().map(|n| build_tag_form(n, details)).collect());
+    details[&node.id].to_bulk_form(children)
+}
+
+async fn fetch_topics(server_manager: &ServerManager) -> Result<Vec<TopicBulkForm>, Box<dyn Error>> {
+    let mut topic_module = TopicModule::new(server_manager);
+    let items = topic_module.list_all().await?;
+    let topic_module = &topic_module;
+    let details: Vec<TopicDetailRes> = fetch_details(&items, |item| async move {
+        topic_module.get(item.id).await.map_err(|e| format!("Get topic '{}' failed. {}", item.name, e))
+    }).await?;
+
+    // 父主题不在导出结果中的主题，作为顶层主题导出，避免被丢弃
+    let ids: HashSet<i32> = details.iter().map(|d| d.id).collect();
+    let mut children_map: HashMap<Option<i32>, Vec<&TopicDetailRes>> = HashMap::new();
+    for d in &details {
+        children_map.entry(d.parent_id.filter(|p| ids.contains(p))).or_default().push(d);
+    }
+
+    Result::Ok(build_topic_forms(Option::None, &children_map))
+}
+
+fn build_topic_forms(parent_id: Option<i32>, children_map: &HashMap<Option<i32>, Vec<&TopicDetailRes>>) -> Vec<TopicBulkForm> {
+    match children_map.get(&parent_id) {
+        Some(items) => items.iter().map(|d| {
+            let children = build_topic_forms(Option::Some(d.id), children_map);
+            d.to_bulk_form(if children.is_empty() { Option::None }else{ Option::Some(children) })
+        }).collect(),
+        None => Vec::new()
+    }
+}
+
+async fn fetch_authors(server_manager: &ServerManager) -> Result<Vec<AuthorBulkForm>, Box<dyn Error>> {
+    let mut author_module = AuthorModule::new(server_manager);
+    let items = author_module.list_all().await?;
+    let author_module = &author_module;
+    fetch_details(&items, |item| async move {
+        author_module.get(item.id).await.map(|d| d.to_bulk_form()).map_err(|e| format!("Get author '{}' failed. {}", item.name, e))
+    }).await
+}
+
+async fn fetch_setting(server_manager: &ServerManager) -> Result<ApplyFileSetting, Box<dyn Error>> {
+    let mut setting_module = SettingModule::new(server_manager);
+
+    Result::Ok(ApplyFileSetting {
+        meta: Option::Some(setting_module.get_meta_option().await?.to_update_form()),
+        query: Option::Some(setting_module.get_query_option().await?.to_update_form()),
+        import: Option::Some(setting_module.get_import_option().await?.to_update_form()),
+        file: Option::Some(setting_module.get_storage_option().await?.to_update_form()),
+        find_similar: Option::Some(setting_module.get_find_similar_option().await?.to_update_form()),
+        source_sites: Option::Some(setting_module.get_source_sites().await?.iter().map(|s| s.to_update_form()).collect())
+    })
+}
+
+fn get_export_format(file: &Option<PathBuf>, format: Option<ExportFormat>) -> Result<ExportFormat, ApplicationError> {
+    if let Some(format) = format {
+        return Result::Ok(format)
+    }
+    match file.as_ref().and_then(|f| f.extension()).map(|e| e.to_string_lossy().to_lowercase()) {
+        None => if file.is_none() {
+            Result::Ok(ExportFormat::Yaml)
+        }else{
+            Result::Err(ApplicationError::new("Cannot infer export format from file name. Please specify --format."))
+        },
+        Some(extension) => match extension.as_str() {
+            "json" => Result::Ok(ExportFormat::Json),
+            "yaml" => Result::Ok(ExportFormat::Yaml),
+            "toml" => Result::Ok(ExportFormat::Toml),
+            _ => Result::Err(ApplicationError::new(&format!("Unsupported file type {}.", extension)))
+        }
+    }
+}
+
+fn write_apply_file(file: &ApplyFile, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+    match format {
+        ExportFormat::Json => Result::Ok(serde_json::to_string_pretty(file)? + "\n"),
+        ExportFormat::Yaml => Result::Ok(serde_yaml::to_string(file)?),
+        ExportFormat::Toml => Result::Ok(toml::to_string_pretty(file)?)
+    }
+}
+
+fn count_tag(tag: &TagBulkForm) -> usize {
+    1 + tag.children.as_ref().map(|c| c.iter().map(|t| count_tag(t)).sum()).unwrap_or(0)
+}
+
+fn count_topic(topic: &TopicBulkForm) -> usize {
+    1 + topic.children.as_ref().map(|c| c.iter().map(|t| count_topic(t)).sum()).unwrap_or(0)
+}
@@ -1,133 +1,205 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use chrono::NaiveDate;
+use serde::Serialize;
 use crate::module::import::{ImportModule, OrderTimeType};
-use super::Context;
+use super::{Context, CommandError, CommandResult};
 
+#[derive(Serialize)]
+struct ListItem {
+    id: i32,
+    file_name: Option<String>,
+    partition_time: String
+}
+
+#[derive(Serialize)]
+struct ListSummary {
+    total: i32
+}
+
+#[derive(Serialize)]
+struct AddItem {
+    file: String,
+    success: bool,
+    error: Option<String>
+}
+
+#[derive(Serialize)]
+struct AddSummary {
+    success: i32,
+    failed: i32
+}
+
+#[derive(Serialize)]
+struct BatchResult {
+    success: bool
+}
+
+#[derive(Serialize)]
+struct SaveErrorItem {
+    import_id: i32,
+    reasons: Vec<&'static str>
+}
+
+#[derive(Serialize)]
+struct SaveSummary {
+    total: i32,
+    failed: usize
+}
 
-pub async fn list(context: &mut Context<'_>) {
+pub async fn list(context: &mut Context<'_>) -> CommandResult {
     if let Err(e) = context.server_manager.waiting_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
 
     let mut import_module = ImportModule::new(context.server_manager);
     let r = match import_module.list().await {
         Err(e) => {
-            eprintln!("Error occrred in requesting. {}", e.to_string());
-            return
+            context.output.error("Error occrred in requesting.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
         },
         Ok(r) => r
     };
-    for item in &r.result {
-        println!("-{:3}| {:50} | {}", item.id, item.file_name.as_ref().unwrap_or(&"".to_string()), item.partition_time)
-    }
-    if r.result.len() > 0 {
-        println!("---");
-    }
-    println!("Total {} result(s).", r.total);
+    let items: Vec<ListItem> = r.result.into_iter().map(|i| ListItem { id: i.id, file_name: i.file_name, partition_time: i.partition_time }).collect();
+    context.output.list(&items, &ListSummary { total: r.total }, |items, summary| {
+        for item in items {
+            println!("-{:3}| {:50} | {}", item.id, item.file_name.as_deref().unwrap_or(""), item.partition_time)
+        }
+        if !items.is_empty() {
+            println!("---");
+        }
+        println!("Total {} result(s).", summary.total);
+    });
+    Result::Ok(())
 }
 
-pub async fn add(context: &mut Context<'_>, files: &Vec<PathBuf>, remove: bool) {
+pub async fn add(context: &mut Context<'_>, files: &Vec<PathBuf>, remove: bool) -> CommandResult {
     if files.len() > 0 {
         if let Err(e) = context.server_manager.waiting_for_start().await {
-            eprintln!("Cannot establish connection to server. {}", e);
-            return
+            context.output.error("Cannot establish connection to server.", e);
+            return Result::Err(CommandError::Connection)
         }
 
-        let mut success = 0;
-        let mut failed = 0;
+        let mut items: Vec<AddItem> = Vec::new();
         let mut import_module = ImportModule::new(context.server_manager);
         for file in files {
             if file.is_dir() {
                 match std::fs::read_dir(file) {
-                    Ok(items) => for item in items {
-                        if let Ok(entry) = item {
-                            if entry.file_type().is_ok_and(|f| f.is_file()) {
-                                let mut f = file.clone();
-                                f.push(&entry.file_name());
-                                if let Err(e) = import_module.add(&f, remove).await {
-                                    println!("\x1b[1;33m{}\x1b[1;31m add failed. {}\x1b[0m", f.to_str().unwrap(), e.to_string());
-                                    failed += 1;
-                                }else{
-                                    println!("\x1b[1;33m{}\x1b[0m added.", f.to_str().unwrap());
-                                    success += 1;
-                                }
-                            }
+                    Ok(entries) => for entry in entries.flatten() {
+                        if entry.file_type().is_ok_and(|f| f.is_file()) {
+                            let f = file.join(entry.file_name());
+                            let item = match import_module.add(&f, remove).await {
+                                Err(e) => AddItem { file: path_str(&f), success: false, error: Option::Some(format!("add failed. {}", e)) },
+                                Ok(_) => AddItem { file: path_str(&f), success: true, error: Option::None }
+                            };
+                            context.output.item(&item, print_add_item);
+                            items.push(item);
                         }
                     },
                     Err(e) => {
-                        println!("\x1b[1;33m{}\x1b[1;31m cannot read dir. {}\x1b[0m", file.to_str().unwrap(), e.to_string());
-                        failed += 1;
+                        let item = AddItem { file: path_str(file), success: false, error: Option::Some(format!("cannot read dir. {}", e)) };
+                        context.output.item(&item, print_add_item);
+                        items.push(item);
                     }
                 }
-                
             }else if file.is_file() {
-                if let Err(e) = import_module.add(file, remove).await {
-                    println!("\x1b[1;33m{}\x1b[1;31m add failed. {}\x1b[0m", file.to_str().unwrap(), e.to_string());
-                    failed += 1;
-                }else{
-                    println!("\x1b[1;33m{}\x1b[0m added.", file.to_str().unwrap());
-                    success += 1;
-                }
+                let item = match import_module.add(file, remove).await {
+                    Err(e) => AddItem { file: path_str(file), success: false, error: Option::Some(format!("add failed. {}", e)) },
+                    Ok(_) => AddItem { file: path_str(file), success: true, error: Option::None }
+                };
+                context.output.item(&item, print_add_item);
+                items.push(item);
             }else{
-                println!("\x1b[1;33m{}\x1b[1;31m unsupported file.\x1b[0m", file.to_str().unwrap());
-                failed += 1;
+                let item = AddItem { file: path_str(file), success: false, error: Option::Some("unsupported file.".to_string()) };
+                context.output.item(&item, print_add_item);
+                items.push(item);
             }
         }
 
-        println!("---");
-        if failed > 0 {
-            println!("Import completed. Success {} files(s), failed \x1b[1;31m{}\x1b[0m files(s).", success, failed);
-        }else{
-            println!("Import completed. Success {} files(s), failed 0 files(s).", success);
-        }
+        let success = items.iter().filter(|i| i.success).count() as i32;
+        let summary = AddSummary { success, failed: items.len() as i32 - success };
+        context.output.summary(&items, &summary, |s| {
+            println!("---");
+            if s.failed > 0 {
+                println!("Import completed. Success {} files(s), failed \x1b[1;31m{}\x1b[0m files(s).", s.success, s.failed);
+            }else{
+                println!("Import completed. Success {} files(s), failed 0 files(s).", s.success);
+            }
+        });
+        CommandError::of_failed(summary.failed as usize)
+    }else{
+        Result::Ok(())
+    }
+}
+
+fn print_add_item(item: &AddItem) {
+    if let Some(e) = &item.error {
+        println!("\x1b[1;33m{}\x1b[1;31m {}\x1b[0m", item.file, e);
+    }else{
+        println!("\x1b[1;33m{}\x1b[0m added.", item.file);
     }
 }
 
-pub async fn batch(context: &mut Context<'_>, partition_time: Option<NaiveDate>, create_time: Option<OrderTimeType>, order_time: Option<OrderTimeType>, analyse_source: bool) {
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+pub async fn batch(context: &mut Context<'_>, partition_time: Option<NaiveDate>, create_time: Option<OrderTimeType>, order_time: Option<OrderTimeType>, analyse_source: bool) -> CommandResult {
     if partition_time.is_some() || create_time.is_some() || order_time.is_some() || analyse_source {
         if let Err(e) = context.server_manager.waiting_for_start().await {
-            eprintln!("Cannot establish connection to server. {}", e);
-            return
+            context.output.error("Cannot establish connection to server.", e);
+            return Result::Err(CommandError::Connection)
         }
 
         let mut import_module = ImportModule::new(context.server_manager);
         match import_module.batch(partition_time, create_time, order_time, analyse_source).await {
-            Err(e) => eprintln!("Error occrred in requesting. {}", e.to_string()),
-            Ok(_) => println!("Batch Succeed.")
-        };
+            Err(e) => {
+                context.output.error("Error occrred in requesting.", &e);
+                Result::Err(CommandError::of_request(e.as_ref()))
+            },
+            Ok(_) => {
+                context.output.result(&BatchResult { success: true }, |_| println!("Batch Succeed."));
+                Result::Ok(())
+            }
+        }
+    }else{
+        Result::Ok(())
     }
 }
 
-pub async fn save(context: &mut Context<'_>) {
+pub async fn save(context: &mut Context<'_>) -> CommandResult {
     if let Err(e) = context.server_manager.waiting_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
 
     let mut import_module = ImportModule::new(context.server_manager);
     let r = match import_module.save().await {
         Err(e) => {
-            eprintln!("Error occrred in requesting. {}", e.to_string());
-            return
+            context.output.error("Error occrred in requesting.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
         },
         Ok(r) => r
     };
-    for e in &r.errors {
-        let mut v = Vec::new();
-        if e.file_not_ready { v.push("File not ready.") }
-        if e.not_existed_clone_image_id.is_some() { v.push("Preference clone image not exist.") }
-        if e.not_existed_collection_id.is_some() { v.push("Preference collection not exist.") }
-        if e.not_existed_book_ids.is_some() { v.push("Preference book not exist.") }
-        if e.not_existed_folder_ids.is_some() { v.push("Preference folder not exist.") }
-        let reason = v.join(" ");
-        
-        println!("-{:3}| {}", e.import_id, reason);
-    }
-    if (&r.errors).len() > 0 {
-        println!("---");
-        println!("{} item(s) saved. \x1b[1;31m{}\x1b[0m item(s) save failed.", r.total, r.errors.len());
-    }else{
-        println!("{} item(s) saved.", r.total);
-    }
-}
\ No newline at end of file
+    let items: Vec<SaveErrorItem> = r.errors.iter().map(|e| {
+        let mut reasons = Vec::new();
+        if e.file_not_ready { reasons.push("File not ready.") }
+        if e.not_existed_clone_image_id.is_some() { reasons.push("Preference clone image not exist.") }
+        if e.not_existed_collection_id.is_some() { reasons.push("Preference collection not exist.") }
+        if e.not_existed_book_ids.is_some() { reasons.push("Preference book not exist.") }
+        if e.not_existed_folder_ids.is_some() { reasons.push("Preference folder not exist.") }
+        SaveErrorItem { import_id: e.import_id, reasons }
+    }).collect();
+    context.output.list(&items, &SaveSummary { total: r.total, failed: items.len() }, |items, summary| {
+        for e in items {
+            println!("-{:3}| {}", e.import_id, e.reasons.join(" "));
+        }
+        if summary.failed > 0 {
+            println!("---");
+            println!("{} item(s) saved. \x1b[1;31m{}\x1b[0m item(s) save failed.", summary.total, summary.failed);
+        }else{
+            println!("{} item(s) saved.", summary.total);
+        }
+    });
+    CommandError::of_failed(items.len())
+}
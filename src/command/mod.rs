@@ -1,19 +1,59 @@
 pub mod app;
 pub mod apply;
+pub mod export;
 pub mod channel;
 pub mod server;
 pub mod import;
 pub mod source_data;
 pub mod tool;
 
+use std::sync::Arc;
 use crate::module::channel::ChannelManager;
 use crate::module::config::LocalConfig;
 use crate::module::local_data::LocalDataManager;
 use crate::module::server::ServerManager;
+use crate::module::trace::Tracer;
+use crate::utils::error::HttpStatusError;
+use crate::utils::output::Output;
 
 pub struct Context<'t> {
     pub config: LocalConfig,
     pub local_data_manager: &'t LocalDataManager,
     pub channel_manager: &'t ChannelManager<'t>,
-    pub server_manager: &'t mut ServerManager
-}
\ No newline at end of file
+    pub server_manager: &'t mut ServerManager,
+    pub output: Output,
+    pub tracer: Option<Arc<Tracer>>
+}
+// 命令失败的类型。错误信息已由命令通过Output输出，main只根据它决定进程的退出码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    // 无法连接到server，或请求没有得到响应
+    Connection,
+    // 参数、配置或输入文件有误
+    Input,
+    // 执行完成，但其中部分条目失败
+    PartialFailure,
+    // 其他错误
+    Failed
+}
+
+pub type CommandResult = Result<(), CommandError>;
+
+impl CommandError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CommandError::Failed => 1,
+            CommandError::Input => 2,
+            CommandError::Connection => 3,
+            CommandError::PartialFailure => 4
+        }
+    }
+    // 根据请求的错误区分：请求没有送达，或网关返回5xx时为连接问题，否则为Failed
+    pub fn of_request(e: &(dyn std::error::Error + 'static)) -> CommandError {
+        if e.downcast_ref::<reqwest::Error>().is_some() || e.downcast_ref::<HttpStatusError>().is_some_and(|e| e.status >= 500) { CommandError::Connection }else{ CommandError::Failed }
+    }
+    // 有条目失败时为PartialFailure
+    pub fn of_failed(failed: usize) -> CommandResult {
+        if failed > 0 { Result::Err(CommandError::PartialFailure) }else{ Result::Ok(()) }
+    }
+}
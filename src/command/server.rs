@@ -1,69 +1,472 @@
-use crate::{module::server::ServerStatusType, utils::error::ApplicationError};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use std::{path::PathBuf, time::Duration};
+use crate::utils::output::OutputFormat;
+use crate::module::service::{self, ServiceOptions};
+use crate::module::server::{ProcessState, ServerStatusType, ShutdownResult};
+use crate::module::server_log::{LogLevel, LogOptions};
 
-use super::Context;
+use super::{Context, CommandError, CommandResult};
 
 
-pub async fn status(context: &mut Context<'_>) {
-    let stat = context.server_manager.status().await;
-    println!("Running status: {}", stat.status);
-    if stat.status != ServerStatusType::Stop {
-        println!("---");
-        if stat.remote_mode {
-            println!("Mode: Remote ")
+#[derive(Serialize)]
+struct StatusResult {
+    status: String,
+    remote_mode: bool,
+    pid: Option<i64>,
+    host: Option<String>,
+    port: Option<i32>,
+    start_time: Option<i64>,
+    elapsed_seconds: Option<i64>,
+    managed_by: Option<String>,
+    detail: Option<String>
+}
+
+#[derive(Serialize)]
+struct ControlResult {
+    action: &'static str,
+    status: String,
+    message: &'static str
+}
+
+pub async fn status(context: &mut Context<'_>) -> CommandResult {
+    let stat = match context.server_manager.status().await {
+        Ok(s) => s,
+        Err(e) => {
+            context.output.failure("Cannot read server status.", &e);
+            return Result::Err(CommandError::Failed)
         }
-        if let Some(pid) = stat.pid {
-            println!("PID: {}", pid)
+    };
+    // 已退出或无法访问的server不输出运行时长与托管方式
+    let alive = !matches!(stat.status, ServerStatusType::Stop | ServerStatusType::Crashed | ServerStatusType::Stale);
+    let result = StatusResult {
+        status: stat.status.to_string(),
+        remote_mode: stat.remote_mode,
+        pid: stat.pid,
+        host: stat.host,
+        port: stat.port,
+        start_time: stat.start_time,
+        elapsed_seconds: stat.start_time.filter(|_| alive).map(|t| (chrono::Utc::now().timestamp_millis() - t) / 1000),
+        managed_by: stat.pid.filter(|_| alive).and_then(|pid| service::systemd_unit(pid, context.channel_manager.current_channel())).map(|u| format!("systemd ({})", u)),
+        detail: stat.detail
+    };
+    context.output.result(&result, |r| {
+        println!("Running status: {}", r.status);
+        if let Some(detail) = &r.detail {
+            println!("{}", detail)
         }
-        if let Some(host) = stat.host {
-            println!("Host: {}", host)
+        if r.status != ServerStatusType::Stop.to_string() {
+            println!("---");
+            if r.remote_mode {
+                println!("Mode: Remote ")
+            }
+            if let Some(pid) = r.pid {
+                println!("PID: {}", pid)
+            }
+            if let Some(host) = &r.host {
+                println!("Host: {}", host)
+            }
+            if let Some(port) = r.port {
+                println!("Port: {}", port)
+            }
+            if let Some(managed_by) = &r.managed_by {
+                println!("Managed By: {}", managed_by)
+            }
+            if let Some(delta) = r.elapsed_seconds {
+                let sec = delta % 60;
+                let min = (delta % 3600) / 60;
+                let hour = delta / 3600;
+                println!("Elapsed Time: {:02}:{:02}:{:02}", hour, min, sec);
+            }
         }
-        if let Some(port) = stat.port {
-            println!("Port: {}", port)
+    });
+    Result::Ok(())
+}
+
+pub async fn start(context: &mut Context<'_>, no_spawn: bool) -> CommandResult {
+    if no_spawn {
+        // server由systemd等外部方式启动，此时PID文件可能尚未写入，只等待其就绪
+        context.output.message("Waiting...");
+        if let Err(e) = context.server_manager.waiting_for_ready().await {
+            context.output.error("Cannot establish connection to server.", e);
+            return Result::Err(CommandError::Connection)
         }
-        if let Some(t) = stat.start_time {
-            let now = chrono::Utc::now().timestamp_millis();
-            let delta = (now - t) / 1000;
-            let sec = delta % 60;
-            let min = (delta % 3600) / 60;
-            let hour = delta / 3600;
-            println!("Elapsed Time: {:02}:{:02}:{:02}", hour, min, sec);
+    }else if matches!(read_status(context).await?, ServerStatusType::Stop | ServerStatusType::Crashed | ServerStatusType::Stale) {
+        context.output.message("Starting...");
+        if let Err(e) = context.server_manager.waiting_for_start().await {
+            context.output.error("Cannot establish connection to server.", e);
+            return Result::Err(CommandError::Connection)
         }
     }
+    if let Err(e) = context.server_manager.permanent(true).await {
+        context.output.error("Error occurred when set permanent.", &e);
+        return Result::Err(CommandError::of_request(e.as_ref()))
+    }
+    let result = ControlResult { action: "start", status: ServerStatusType::Running.to_string(), message: "Backend service is running in permanent mode." };
+    context.output.result(&result, |r| println!("{}", r.message));
+    Result::Ok(())
 }
 
-pub async fn start(context: &mut Context<'_>) {
-    if context.server_manager.status().await.status == ServerStatusType::Stop {
-        println!("Starting...");
-        if let Err(e) = context.server_manager.waiting_for_start().await {
-            eprintln!("Cannot establish connection to server. {}", e);
-            return
+pub async fn stop(context: &mut Context<'_>, now: bool, timeout: u64) -> CommandResult {
+    let status = read_status(context).await?;
+    // 端口冲突时无法请求server退出permanent模式，直接结束进程
+    if now || status == ServerStatusType::Stale {
+        return stop_now(context, status, timeout).await.map(|message| {
+            let result = ControlResult { action: "stop", status: ServerStatusType::Stop.to_string(), message };
+            context.output.result(&result, |r| println!("{}", r.message));
+        })
+    }
+    if !status.is_stopped() {
+        if let Err(e) = context.server_manager.permanent(false).await {
+            context.output.error("Error occurred when set permanent.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
         }
+        let result = ControlResult { action: "stop", status: status.to_string(), message: "Backend service exited permanent mode." };
+        context.output.result(&result, |r| println!("{}", r.message));
+    }else{
+        let result = ControlResult { action: "stop", status: status.to_string(), message: "" };
+        context.output.result(&result, |_| {});
     }
-    context.server_manager.permanent(true).await;
-    println!("Backend service is running in permanent mode.")
+    Result::Ok(())
 }
 
-pub async fn stop(context: &mut Context<'_>) {
-    if context.server_manager.status().await.status != ServerStatusType::Stop {
-        context.server_manager.permanent(false).await;
-        println!("Backend service exited permanent mode.")
+pub async fn restart(context: &mut Context<'_>, timeout: u64) -> CommandResult {
+    let status = read_status(context).await?;
+    let message = stop_now(context, status, timeout).await?;
+    context.output.message(message);
+    context.output.message("Starting...");
+    if let Err(e) = context.server_manager.waiting_for_start().await {
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
+    }
+    if let Err(e) = context.server_manager.permanent(true).await {
+        context.output.error("Error occurred when set permanent.", &e);
+        return Result::Err(CommandError::of_request(e.as_ref()))
     }
+    let result = ControlResult { action: "restart", status: ServerStatusType::Running.to_string(), message: "Backend service is restarted in permanent mode." };
+    context.output.result(&result, |r| println!("{}", r.message));
+    Result::Ok(())
 }
 
-pub async fn kill(context: &mut Context<'_>) {
-    if context.server_manager.status().await.status != ServerStatusType::Stop {
-        if let Err(e) = context.server_manager.kill() {
-            if let Some(e) = e.downcast_ref::<ApplicationError>() {
-                eprintln!("{}", e.message)
+#[derive(Serialize)]
+struct ServiceUnitResult {
+    name: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>
+}
+
+#[derive(Serialize)]
+struct InstallServiceResult {
+    installed: bool,
+    enabled: bool,
+    units: Vec<ServiceUnitResult>
+}
+
+pub fn install_service(context: &Context<'_>, install: bool, enable: bool, timer_command: Option<String>, timer_calendar: String) -> CommandResult {
+    if !cfg!(target_os = "linux") {
+        context.output.error("Installing service is only supported on Linux with systemd.", "");
+        return Result::Err(CommandError::Input)
+    }
+    if context.server_manager.login_info().is_ok_and(|i| i.mode == "remote") {
+        context.output.error("This channel is runnning in remote mode. Cannot install service for remote server.", "");
+        return Result::Err(CommandError::Input)
+    }
+    let cli_path = match std::env::current_exe() {
+        Ok(p) => p,
+        Err(e) => {
+            context.output.error("Cannot locate hedge executable.", e);
+            return Result::Err(CommandError::Failed)
+        }
+    };
+    let config_path = std::env::var_os("LOCAL_CONFIG_PATH").map(PathBuf::from).map(|p| std::fs::canonicalize(&p).unwrap_or(p));
+    let options = ServiceOptions { channel: context.channel_manager.current_channel(), cli_path: &cli_path, config_path: config_path.as_deref(), timer_command: timer_command.as_deref(), timer_calendar: &timer_calendar };
+    let units = service::generate_units(&context.server_manager.server_command(), &options);
+    let install = install || enable;
+    let paths = if install {
+        match service::install_units(&units, enable) {
+            Ok(p) => p.iter().map(|p| Option::Some(p.to_string_lossy().to_string())).collect(),
+            Err(e) => {
+                context.output.failure("Cannot install service.", &e);
+                return Result::Err(CommandError::Failed)
+            }
+        }
+    }else{
+        vec![Option::None; units.files().len()]
+    };
+    let result = InstallServiceResult {
+        installed: install,
+        enabled: enable,
+        units: units.files().into_iter().zip(paths).map(|(u, path)| ServiceUnitResult { name: u.name.clone(), content: u.content.clone(), path }).collect()
+    };
+    context.output.result(&result, |r| {
+        if !r.installed {
+            for u in &r.units {
+                println!("# {}", u.name);
+                println!("{}", u.content);
+            }
+            println!("---");
+            println!("Use --install to write them into {}, or --enable to also start them.", service::user_unit_dir().map(|d| d.to_string_lossy().to_string()).unwrap_or("~/.config/systemd/user".to_string()));
+        }else{
+            for u in &r.units {
+                println!("Installed {}", u.path.as_deref().unwrap_or(&u.name));
+            }
+            if r.enabled {
+                println!("Service {} is enabled and started.", r.units[0].name);
             }else{
-                eprintln!("{}", e)
+                println!("Run 'systemctl --user enable --now {}' to start it.", r.units[0].name);
+            }
+            println!("Run 'loginctl enable-linger' to keep it running after logging out.");
+        }
+    });
+    Result::Ok(())
+}
+
+#[derive(Serialize)]
+struct SuperviseEvent {
+    time: String,
+    event: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<i64>,
+    message: String
+}
+
+// 前台运行的守护模式：保持server处于permanent模式，定期检查健康状态，进程退出或长时间不健康时按退避时间重新启动。
+// 作为systemd服务运行时，停止服务会向整个cgroup发送SIGTERM，server也会随之正常退出
+pub async fn supervise(context: &mut Context<'_>, interval: u64, max_backoff: u64, unhealthy_timeout: u64) -> CommandResult {
+    if context.output.format == OutputFormat::Json {
+        context.output.error("Supervise runs until it is stopped and cannot output a single JSON result.", "Use --output ndjson to output events line by line.");
+        return Result::Err(CommandError::Input)
+    }
+    if context.server_manager.login_info().is_ok_and(|i| i.mode == "remote") {
+        context.output.error("This channel is runnning in remote mode. Cannot supervise remote server.", "");
+        return Result::Err(CommandError::Input)
+    }
+    let stable_time = Duration::from_secs(60);
+    let mut backoff = Duration::from_secs(1);
+    let mut last_event = "";
+    let mut started_at = Option::None;
+    let mut unhealthy_since: Option<std::time::Instant> = Option::None;
+    let mut first_start = true;
+    supervise_event(context, &mut last_event, "supervise", Option::None, &format!("Supervising channel {}, checking every {}s.", context.channel_manager.current_channel(), interval));
+    loop {
+        let state = match context.server_manager.process_state() {
+            Ok(s) => s,
+            Err(e) => {
+                supervise_event(context, &mut last_event, "error", Option::None, &format!("Cannot read server process: {}", e));
+                async_std::task::sleep(Duration::from_secs(interval)).await;
+                continue
+            }
+        };
+        match state {
+            ProcessState::Alive(pid) => {
+                let unhealthy = match context.server_manager.status().await.map(|s| s.status) {
+                    Ok(ServerStatusType::Running) => {
+                        // server可能被其他客户端或重启后取消了permanent模式，每次检查都重新设置
+                        match context.server_manager.permanent(true).await {
+                            Ok(_) => supervise_event(context, &mut last_event, "running", Option::Some(pid), "Server is running."),
+                            Err(e) => supervise_event(context, &mut last_event, "error", Option::Some(pid), &format!("Cannot set permanent mode: {}", e))
+                        }
+                        // 稳定运行一段时间后，重置退避时间
+                        if started_at.is_some_and(|t: std::time::Instant| t.elapsed() >= stable_time) {
+                            backoff = Duration::from_secs(1);
+                            started_at = Option::None;
+                        }
+                        Option::None
+                    },
+                    Ok(status) => Option::Some(format!("Server process is alive but status is {}.", status)),
+                    Err(e) => Option::Some(format!("Cannot read server status: {}", e))
+                };
+                first_start = false;
+                if let Some(message) = unhealthy {
+                    let since = *unhealthy_since.get_or_insert_with(std::time::Instant::now);
+                    if since.elapsed() < Duration::from_secs(unhealthy_timeout) {
+                        supervise_event(context, &mut last_event, "unhealthy", Option::Some(pid), &message);
+                    }else{
+                        // 长时间无法恢复时结束进程，下一轮检查按退避时间重新启动
+                        unhealthy_since = Option::None;
+                        supervise_event(context, &mut last_event, "restarting", Option::Some(pid), &format!("Server is unhealthy for {}s. Stopping it to restart.", unhealthy_timeout));
+                        if let Err(e) = context.server_manager.shutdown(Duration::from_secs(30)).await {
+                            supervise_event(context, &mut last_event, "error", Option::Some(pid), &format!("Cannot stop server: {}", e));
+                        }
+                        continue
+                    }
+                }else{
+                    unhealthy_since = Option::None;
+                }
+            },
+            ProcessState::Absent | ProcessState::Dead(_) | ProcessState::Foreign(_) => {
+                // 首次启动不必等待
+                if first_start {
+                    supervise_event(context, &mut last_event, "starting", Option::None, "Starting server.");
+                }else{
+                    let (event, message) = match state {
+                        ProcessState::Dead(pid) => ("crashed", format!("Server process {} exited unexpectedly. Restarting in {}s.", pid, backoff.as_secs())),
+                        ProcessState::Foreign(pid) => ("crashed", format!("Server process {} is gone and its PID is reused by another program. Restarting in {}s.", pid, backoff.as_secs())),
+                        _ => ("stopped", format!("Server is stopped. Restarting in {}s.", backoff.as_secs()))
+                    };
+                    supervise_event(context, &mut last_event, event, Option::None, &message);
+                    async_std::task::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, Duration::from_secs(max_backoff));
+                }
+                first_start = false;
+                match start_supervised(context).await {
+                    Ok(_) => {
+                        started_at = Option::Some(std::time::Instant::now());
+                        let pid = match context.server_manager.process_state() { Ok(ProcessState::Alive(pid)) => Option::Some(pid), _ => Option::None };
+                        supervise_event(context, &mut last_event, "running", pid, "Server is started in permanent mode.");
+                    },
+                    Err(e) => supervise_event(context, &mut last_event, "error", Option::None, &format!("Cannot start server: {}", e))
+                }
+                continue
             }
+        }
+        async_std::task::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+async fn start_supervised(context: &mut Context<'_>) -> Result<(), Box<dyn std::error::Error>> {
+    context.server_manager.clear_stale_pid_file()?;
+    context.server_manager.waiting_for_start().await?;
+    context.server_manager.permanent(true).await
+}
+
+// 只在状态变化时输出
+fn supervise_event(context: &Context<'_>, last_event: &mut &'static str, event: &'static str, pid: Option<i64>, message: &str) {
+    if *last_event == event && event != "error" {
+        return
+    }
+    *last_event = event;
+    let item = SuperviseEvent { time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(), event, pid, message: message.to_string() };
+    context.output.item(&item, |e| println!("[{}] {}", e.time, e.message));
+}
+
+// 先退出permanent模式，再请求server正常退出，返回结束时的说明
+async fn stop_now(context: &mut Context<'_>, status: ServerStatusType, timeout: u64) -> Result<&'static str, CommandError> {
+    if status.is_stopped() {
+        return Result::Ok("Backend service is not running.")
+    }
+    if status == ServerStatusType::Running {
+        // 失败也不影响后续的退出
+        if let Err(e) = context.server_manager.permanent(false).await {
+            context.output.error("Error occurred when set permanent.", &e);
+        }
+    }
+    context.output.message("Stopping...");
+    match context.server_manager.shutdown(Duration::from_secs(timeout)).await {
+        Ok(ShutdownResult::NotRunning) => Result::Ok("Backend service is not running."),
+        Ok(ShutdownResult::Graceful) => Result::Ok("Backend service stopped."),
+        Ok(ShutdownResult::Killed) => {
+            context.output.error(&format!("Backend service did not stop in {}s and is killed.", timeout), "");
+            Result::Ok("Backend service is killed after timeout.")
+        },
+        Err(e) => {
+            context.output.failure("Cannot stop backend service.", &e);
+            Result::Err(CommandError::Failed)
+        }
+    }
+}
+
+// 生成的systemd unit在启动server前调用，与CLI启动server时一样轮转server.log
+pub fn rotate_log(context: &Context<'_>) -> CommandResult {
+    if let Err(e) = context.server_manager.rotate_log() {
+        context.output.failure("Cannot rotate server log.", &e);
+        return Result::Err(CommandError::Failed)
+    }
+    context.output.message("Server log is rotated.");
+    Result::Ok(())
+}
+
+pub async fn kill(context: &mut Context<'_>) -> CommandResult {
+    let status = read_status(context).await?;
+    if !status.is_stopped() {
+        if let Err(e) = context.server_manager.kill() {
+            context.output.error("Kill backend service failed.", e);
+            return Result::Err(CommandError::Failed)
         }else{
-            println!("Backend service is killed.")
+            let result = ControlResult { action: "kill", status: ServerStatusType::Stop.to_string(), message: "Backend service is killed." };
+            context.output.result(&result, |r| println!("{}", r.message));
         }
+    }else{
+        let result = ControlResult { action: "kill", status: status.to_string(), message: "" };
+        context.output.result(&result, |_| {});
+    }
+    Result::Ok(())
+}
+
+pub async fn log(context: &Context<'_>, follow: bool, lines: Option<usize>, since: Option<String>, grep: Option<String>, level: Option<LogLevel>) -> CommandResult {
+    let since = match since.as_deref().map(parse_since) {
+        Some(Some(t)) => Option::Some(t),
+        Some(None) => {
+            context.output.error(&format!("Invalid time '{}' of --since.", since.unwrap_or_default()), "Use \"2024-01-01 12:00:00\", \"2024-01-01\" or relative time as \"30s\", \"30m\", \"2h\", \"1d\".");
+            return Result::Err(CommandError::Input)
+        },
+        None => Option::None
+    };
+    let options = LogOptions { follow, lines, since, grep, level };
+    if let Err(e) = context.server_manager.log(&options).await {
+        context.output.failure("Cannot read server log.", &e);
+        return Result::Err(CommandError::Failed)
+    }
+    Result::Ok(())
+}
+
+// 支持绝对时间与相对于现在的时间
+fn parse_since(since: &str) -> Option<NaiveDateTime> {
+    let since = since.trim();
+    if let Ok(t) = NaiveDateTime::parse_from_str(since, "%Y-%m-%d %H:%M:%S") {
+        return Option::Some(t)
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        return d.and_hms_opt(0, 0, 0)
     }
+    let unit = since.chars().last()?;
+    let value: i64 = since[..since.len() - unit.len_utf8()].parse().ok()?;
+    let delta = match unit {
+        's' => chrono::Duration::try_seconds(value)?,
+        'm' => chrono::Duration::try_minutes(value)?,
+        'h' => chrono::Duration::try_hours(value)?,
+        'd' => chrono::Duration::try_days(value)?,
+        _ => return Option::None
+    };
+    Option::Some(chrono::Local::now().naive_local() - delta)
 }
 
-pub fn log(context: &Context<'_>) {
-    context.server_manager.log();
-}
\ No newline at end of file
+async fn read_status(context: &mut Context<'_>) -> Result<ServerStatusType, CommandError> {
+    match context.server_manager.status().await {
+        Ok(s) => Result::Ok(s.status),
+        Err(e) => {
+            context.output.failure("Cannot read server status.", &e);
+            Result::Err(CommandError::Failed)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_since_accepts_absolute_time() {
+        let expected = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+        assert_eq!(parse_since("2024-01-02 03:04:05"), Option::Some(expected));
+        assert_eq!(parse_since(" 2024-01-02 "), NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(0, 0, 0));
+    }
+
+    #[test]
+    fn parse_since_accepts_relative_time() {
+        let before = chrono::Local::now().naive_local();
+        let since = parse_since("2h").unwrap();
+        let after = chrono::Local::now().naive_local();
+        assert!(since >= before - chrono::Duration::hours(2) && since <= after - chrono::Duration::hours(2));
+        assert!(parse_since("30s").is_some());
+        assert!(parse_since("30m").is_some());
+        assert!(parse_since("1d").is_some());
+    }
+
+    #[test]
+    fn parse_since_rejects_invalid_input() {
+        for since in ["", "h", "2w", "xh", "2024-13-01", "一h"] {
+            assert_eq!(parse_since(since), Option::None, "{}", since);
+        }
+    }
+}
@@ -1,224 +1,321 @@
-use crate::{module::{api::source_data::SourceDataModule, download::DownloadModule, connect::ConnectModule}, utils::error::ApiResultError};
-use super::Context;
+use serde::Serialize;
+use crate::{module::{api::source_data::SourceDataModule, download::{DownloadModule, DownloadResult}, connect::ConnectModule}, utils::error::ApiResultError};
+use super::{Context, CommandError, CommandResult};
 
+#[derive(Serialize)]
+struct QueryItem {
+    site: String,
+    site_name: String,
+    source_id: i64,
+    status: String,
+    tag_count: i32,
+    book_count: i32,
+    relation_count: i32
+}
+
+#[derive(Serialize)]
+struct QuerySummary {
+    total: i32,
+    offset: u32,
+    count: usize
+}
+
+#[derive(Serialize)]
+struct ProcessItem {
+    index: usize,
+    time: String,
+    site: Option<String>,
+    source_id: Option<i64>,
+    status: ProcessStatus,
+    time_cost: Option<i64>,
+    retry_count: Option<i32>,
+    info: Option<String>,
+    error: Option<String>
+}
+
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ProcessStatus {
+    Success,
+    UpdateFailed,
+    Created,
+    Updated,
+    NotEditedUpdated,
+    Skipped,
+    Failed
+}
 
-pub async fn query(context: &mut Context<'_>, hql: &str, offset: u32, limit: u32) {
+#[derive(Serialize)]
+struct ProcessSummary {
+    total: Option<i32>,
+    success: usize,
+    failed: usize
+}
+
+pub async fn query(context: &mut Context<'_>, hql: &str, offset: u32, limit: u32) -> CommandResult {
     if let Err(e) = context.server_manager.waiting_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
 
     let mut source_data_module = SourceDataModule::new(context.server_manager);
     let r = match source_data_module.query(Option::Some(hql), Option::None, Option::None, Option::Some(offset), Option::Some(limit)).await {
         Err(e) => {
-            eprintln!("Error occrred in requesting. {}", e.to_string());
-            return
+            context.output.error("Error occrred in requesting.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
         },
         Ok(r) => r
     };
-    let site_max_len = (&r.result).iter().map(|f| f.site_name.len()).max().unwrap_or(20);
-    let id_max_len = (&r.result).iter().map(|f| f.source_id.to_string().len()).max().unwrap_or(10);
-    for item in &r.result {
-        let mut v = Vec::new();
-        if item.tag_count > 0 { v.push(format!("{} tag(s)", item.tag_count)) }
-        if item.book_count > 0 { v.push(format!("{} book(s)", item.book_count)) }
-        if item.relation_count > 0 { v.push(format!("{} relation(s)", item.relation_count)) }
-        let description = v.join(", ");
-        println!("- {:>site_max_len$} | {:>id_max_len$} | ({}) {}", item.site_name, item.source_id, item.status, description, site_max_len = site_max_len, id_max_len = id_max_len);
-    }
-    if r.result.len() > 0 {
-        println!("---");
-    }
-    println!("Total {} result(s), current {} to {}.", r.total, offset + 1, offset + r.result.len() as u32);
+    let items: Vec<QueryItem> = r.result.into_iter().map(|i| QueryItem { site: i.site, site_name: i.site_name, source_id: i.source_id, status: i.status, tag_count: i.tag_count, book_count: i.book_count, relation_count: i.relation_count }).collect();
+    let summary = QuerySummary { total: r.total, offset, count: items.len() };
+    context.output.list(&items, &summary, |items, summary| {
+        let site_max_len = items.iter().map(|f| f.site_name.len()).max().unwrap_or(20);
+        let id_max_len = items.iter().map(|f| f.source_id.to_string().len()).max().unwrap_or(10);
+        for item in items {
+            let mut v = Vec::new();
+            if item.tag_count > 0 { v.push(format!("{} tag(s)", item.tag_count)) }
+            if item.book_count > 0 { v.push(format!("{} book(s)", item.book_count)) }
+            if item.relation_count > 0 { v.push(format!("{} relation(s)", item.relation_count)) }
+            let description = v.join(", ");
+            println!("- {:>site_max_len$} | {:>id_max_len$} | ({}) {}", item.site_name, item.source_id, item.status, description, site_max_len = site_max_len, id_max_len = id_max_len);
+        }
+        if !items.is_empty() {
+            println!("---");
+        }
+        println!("Total {} result(s), current {} to {}.", summary.total, summary.offset + 1, summary.offset + summary.count as u32);
+    });
+    Result::Ok(())
 }
 
-pub async fn download(context: &mut Context<'_>) {
+pub async fn download(context: &mut Context<'_>) -> CommandResult {
     let sites: Vec<&str> = context.config.download.available_sites.iter().map(|f| f.site.as_str()).collect();
     if sites.len() <= 0 {
-        eprintln!("Available sites not configured.");
-        return
+        context.output.error("Available sites not configured.", "");
+        return Result::Err(CommandError::Input)
     }
 
     if let Err(e) = context.server_manager.maintaining_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
 
     let mut source_data_module = SourceDataModule::new(context.server_manager);
     let r = match source_data_module.query(Option::None, Option::Some(vec!["NOT_EDITED", "ERROR"]), Option::Some(sites), Option::None, Option::Some(1000)).await {
         Err(e) => {
-            eprintln!("Error occrred in requesting. {}", e.to_string());
-            return
+            context.output.error("Error occrred in requesting.", &e);
+            return Result::Err(CommandError::of_request(e.as_ref()))
         },
         Ok(r) => r
     };
     if r.result.len() <= 0 {
-        println!("Total {} result(s) found.", r.total);
-        return
+        let summary = ProcessSummary { total: Option::Some(r.total), success: 0, failed: 0 };
+        context.output.list::<ProcessItem, _>(&[], &summary, |_, s| println!("Total {} result(s) found.", s.total.unwrap_or(0)));
+        return Result::Ok(())
     }
 
-    println!("Total {} result(s) found. Current processing {} result(s).", r.total, r.result.len());
-    println!("---");
-    
-    let download_module = DownloadModule::new(&context.config);
+    context.output.message(&format!("Total {} result(s) found. Current processing {} result(s).", r.total, r.result.len()));
+    context.output.message("---");
+
+    let mut download_module = match DownloadModule::new(&context.config) {
+        Ok(d) => d,
+        Err(e) => {
+            context.output.failure("Cannot prepare download.", &e);
+            return Result::Err(if e.is_format() { CommandError::Input }else{ CommandError::Failed })
+        }
+    };
+    if let Some(tracer) = &context.tracer {
+        download_module.add_middleware(tracer.clone());
+    }
 
+    let lifetime = context.server_manager.lifetime_watch();
     let result_count = r.result.len();
     let result_count_str_len = result_count.to_string().len();
-    let mut index = 1;
-    let mut success = 0;
-    let mut failed = 0;
-    for item in &r.result {
+    let mut items: Vec<ProcessItem> = Vec::with_capacity(result_count);
+    for (i, item) in r.result.iter().enumerate() {
+        // 与server的连接丢失时不再开始新的下载，已完成的条目照常汇总
+        if lifetime.lost().is_some() {
+            break
+        }
+        let index = i + 1;
         //tips: 暂时没有需要additional info的实现。如果有实现，需要根据config的配置，决定哪些需要附加信息，然后对此site查询详情
         let dn = download_module.download(&item.site, item.source_id, Option::None).await;
 
-        let date = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-        print!("{} | {:>rc_len$}/{} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", date, index, result_count, item.site, item.source_id, rc_len = result_count_str_len);
-
-        match dn {
+        let mut process_item = ProcessItem { index, time: now(), site: Option::Some(item.site.clone()), source_id: Option::Some(item.source_id), status: ProcessStatus::Failed, time_cost: Option::None, retry_count: Option::None, info: Option::None, error: Option::None };
+        let time_cost = match dn {
             Ok((result, info)) => {
                 let form = result.to_update_form();
-
+                process_item.time_cost = Option::Some(info.time_cost);
+                process_item.retry_count = Option::Some(info.retry_count);
                 match source_data_module.update(&item.site, item.source_id, &form).await {
-                    Ok(()) => {
-                        println!("\x1b[1;32m Success (in {:.2}s, retry {} time(s))\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count);
-                        success += 1;
-                    },
+                    Ok(()) => process_item.status = ProcessStatus::Success,
                     Err(e) => {
-                        println!("\x1b[1;31m Success (in {:.2}s, retry {} time(s)), But update failed: {}\x1b[0m", (info.time_cost as f64) / 1000.0, info.retry_count, e);
-                        failed += 1;
+                        process_item.status = ProcessStatus::UpdateFailed;
+                        process_item.error = Option::Some(e.to_string());
                     }
                 }
-
-                if index < result_count {
-                    download_module.wait(info.time_cost).await;
-                }
+                info.time_cost
             },
             Err(e) => {
-                println!("\x1b[1;31m Failed: {}\x1b[0m", e);
-                failed += 1;
+                process_item.error = Option::Some(e.to_string());
+                0
+            }
+        };
 
-                if index < result_count {
-                    download_module.wait(0).await;
-                }
+        context.output.item(&process_item, |p| {
+            print!("{} | {:>rc_len$}/{} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", p.time, p.index, result_count, item.site, item.source_id, rc_len = result_count_str_len);
+            let time_cost = (p.time_cost.unwrap_or(0) as f64) / 1000.0;
+            match p.status {
+                ProcessStatus::Success => println!("\x1b[1;32m Success (in {:.2}s, retry {} time(s))\x1b[0m", time_cost, p.retry_count.unwrap_or(0)),
+                ProcessStatus::UpdateFailed => println!("\x1b[1;31m Success (in {:.2}s, retry {} time(s)), But update failed: {}\x1b[0m", time_cost, p.retry_count.unwrap_or(0), p.error.as_deref().unwrap_or("")),
+                _ => println!("\x1b[1;31m Failed: {}\x1b[0m", p.error.as_deref().unwrap_or(""))
             }
+        });
+        items.push(process_item);
+
+        if index < result_count {
+            download_module.wait(time_cost).await;
         }
-        index += 1;
     }
-    
-    println!("---");
-    if failed > 0 {
-        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", success, failed);
-    }else{
-        println!("Processing completed. Success {} item(s), failed 0 item(s).", success);
+
+    let success = items.iter().filter(|i| i.status == ProcessStatus::Success).count();
+    let summary = ProcessSummary { total: Option::Some(r.total), success, failed: items.len() - success };
+    context.output.summary(&items, &summary, |s| {
+        println!("---");
+        print_process_summary(s)
+    });
+    if let Some(reason) = lifetime.lost() {
+        context.output.error("Lost connection to server.", reason);
+        return Result::Err(CommandError::Connection)
     }
+    CommandError::of_failed(summary.failed)
 }
 
-pub async fn connect(context: &mut Context<'_>, split: &Vec<String>, limit: Option<u32>, update: bool, verbose: bool) {
-    if split.len() <= 0 { 
-        eprintln!("Must specify at least one split.");
-        return
+pub async fn connect(context: &mut Context<'_>, split: &Vec<String>, limit: Option<u32>, update: bool, verbose: bool) -> CommandResult {
+    if split.len() <= 0 {
+        context.output.error("Must specify at least one split.", "");
+        return Result::Err(CommandError::Input)
     }
     if let Err(e) = context.server_manager.maintaining_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
     let mut source_data_module = SourceDataModule::new(&context.server_manager);
     let connect_module = ConnectModule::new(&context.config);
-    
+
     let mut conn = match connect_module.connect() {
         Ok(c) => c,
         Err(e) => {
-            eprintln!("Error occrred in connecting. {}", e.to_string());
-            return
+            context.output.error("Error occrred in connecting.", e);
+            return Result::Err(CommandError::Failed)
         }
     };
     let mut stat = match conn.statement(split, limit) {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("Error occrred in statement preparing. {}", e.to_string());
-            return
+            context.output.error("Error occrred in statement preparing.", e);
+            return Result::Err(CommandError::Failed)
         }
     };
 
-    let mut index = 0;
-    let mut success = 0;
+    let lifetime = context.server_manager.lifetime_watch();
+    let mut items: Vec<ProcessItem> = Vec::new();
     while let Some((identity, result)) = stat.next() {
-        if verbose {
-            let date = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-            if let Some((site, id, _)) = &identity {
-                print!("{} | {:>4} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", date, index + 1, site, id);
+        if lifetime.lost().is_some() {
+            break
+        }
+        let mut process_item = ProcessItem { index: items.len() + 1, time: now(), site: identity.as_ref().map(|(s, _, _)| s.clone()), source_id: identity.as_ref().map(|(_, i, _)| *i), status: ProcessStatus::Failed, time_cost: Option::None, retry_count: Option::None, info: Option::None, error: Option::None };
+        match (&identity, &result) {
+            (Some((site, id, _)), Ok(result)) => {
+                match connect_one(&mut source_data_module, site, *id, result, update).await {
+                    Ok(status) => {
+                        process_item.status = status;
+                        process_item.info = Option::Some(result.info());
+                    },
+                    Err(e) => process_item.error = Option::Some(e)
+                }
+            },
+            (None, Ok(_)) => process_item.error = Option::Some("Record parse error: identity is missing.".to_string()),
+            (_, Err(e)) => process_item.error = Option::Some(format!("Record parse error: {}", e))
+        }
+
+        // text格式下只有verbose时才逐条输出
+        if verbose || !context.output.is_text() {
+            context.output.item(&process_item, |p| {
+                if let (Some(site), Some(id)) = (&p.site, &p.source_id) {
+                    print!("{} | {:>4} \x1b[1;33m| {:16} | {:>12} |\x1b[0m", p.time, p.index, site, id);
+                }else{
+                    print!("{} | {:>4} \x1b[1;33m|\x1b[0m", p.time, p.index);
+                }
+                let info = p.info.as_deref().unwrap_or("");
+                match p.status {
+                    ProcessStatus::Created => println!("\x1b[1;32m Created. {}\x1b[0m", info),
+                    ProcessStatus::Updated => println!("\x1b[1;32m Updated. {}\x1b[0m", info),
+                    ProcessStatus::NotEditedUpdated => println!("\x1b[1;32m NOT_EDITED exists, Updated. {}\x1b[0m", info),
+                    ProcessStatus::Skipped => println!("\x1b[1;33m Already exists, skip it.\x1b[0m"),
+                    _ => println!("\x1b[1;31m Failed. {}\x1b[0m", p.error.as_deref().unwrap_or(""))
+                }
+            });
+        }
+        items.push(process_item);
+    }
+
+    let success = items.iter().filter(|i| i.status != ProcessStatus::Failed).count();
+    let summary = ProcessSummary { total: Option::None, success, failed: items.len() - success };
+    context.output.summary(&items, &summary, |s| {
+        if verbose { println!("---") }
+        print_process_summary(s)
+    });
+    if let Some(reason) = lifetime.lost() {
+        context.output.error("Lost connection to server.", reason);
+        return Result::Err(CommandError::Connection)
+    }
+    CommandError::of_failed(summary.failed)
+}
+
+async fn connect_one(source_data_module: &mut SourceDataModule<'_>, site: &str, id: i64, result: &DownloadResult, update: bool) -> Result<ProcessStatus, String> {
+    let form = result.to_update_form();
+    match source_data_module.create(site, id, &form).await {
+        Ok(_) => Result::Ok(ProcessStatus::Created),
+        Err(e) => match e.downcast_ref::<ApiResultError>() {
+            Some(e) if e.code == "ALREADY_EXISTS" => if update {
+                match source_data_module.update(site, id, &form).await {
+                    Ok(_) => Result::Ok(ProcessStatus::Updated),
+                    Err(e) => Result::Err(request_error(e))
+                }
             }else{
-                print!("{} | {:>4} \x1b[1;33m|\x1b[0m", date, index + 1);
-            }
-            match &result {
-                Ok(result) => {
-                    let (site, id, _) = &identity.unwrap();
-                    let form = result.to_update_form();
-                    match source_data_module.create(site, *id, &form).await {
-                        Ok(_) => {
-                            println!("\x1b[1;32m Created. {}\x1b[0m", result.info());
-                            success += 1;
-                        },
-                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                            if e.code == "ALREADY_EXISTS" {
-                                if update {
-                                    match source_data_module.update(site, *id, &form).await {
-                                        Ok(_) => {
-                                            println!("\x1b[1;32m Updated. {}\x1b[0m", result.info());
-                                            success += 1;
-                                        },
-                                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                            println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                        }else{
-                                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                        }
-                                    }
-                                }else{
-                                    match source_data_module.get(site, *id).await {
-                                        Ok(sd) => {
-                                            if sd.status == "NOT_EDITED" {
-                                                match source_data_module.update(site, *id, &form).await {
-                                                    Ok(_) => {
-                                                        println!("\x1b[1;32m NOT_EDITED exists, Updated. {}\x1b[0m", result.info());
-                                                        success += 1;
-                                                    },
-                                                    Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                                        println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                                    }else{
-                                                        println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                                    }
-                                                }
-                                            }else{
-                                                println!("\x1b[1;33m Already exists, skip it.\x1b[0m");
-                                                success += 1;
-                                            }
-                                        },
-                                        Err(e) => if let Some(e) = e.downcast_ref::<ApiResultError>() {
-                                            println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                                        }else{
-                                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
-                                        }
-                                    }
-                                }
-                            }else{
-                                println!("\x1b[1;31m Failed. {}\x1b[0m", e)
-                            }
-                        }else{
-                            println!("\x1b[1;31m Failed. Request error: {}\x1b[0m", e)
+                match source_data_module.get(site, id).await {
+                    Ok(sd) => if sd.status == "NOT_EDITED" {
+                        match source_data_module.update(site, id, &form).await {
+                            Ok(_) => Result::Ok(ProcessStatus::NotEditedUpdated),
+                            Err(e) => Result::Err(request_error(e))
                         }
-                    }
-                },
-                Err(e) => println!("\x1b[1;31m Failed. Record parse error: {}\x1b[0m", e)
-            }
+                    }else{
+                        Result::Ok(ProcessStatus::Skipped)
+                    },
+                    Err(e) => Result::Err(request_error(e))
+                }
+            },
+            _ => Result::Err(request_error(e))
         }
+    }
+}
 
-        index += 1;
+fn request_error(e: Box<dyn std::error::Error>) -> String {
+    if let Some(e) = e.downcast_ref::<ApiResultError>() {
+        e.to_string()
+    }else{
+        format!("Request error: {}", e)
     }
+}
 
-    if verbose { println!("---") }
-    if index - success > 0 {
-        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", success, index - success);
+fn print_process_summary(summary: &ProcessSummary) {
+    if summary.failed > 0 {
+        println!("Processing completed. Success {} item(s), failed \x1b[1;31m{}\x1b[0m item(s).", summary.success, summary.failed);
     }else{
-        println!("Processing completed. Success {} item(s), failed 0 item(s).", success);
+        println!("Processing completed. Success {} item(s), failed 0 item(s).", summary.success);
     }
-}
\ No newline at end of file
+}
+
+fn now() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
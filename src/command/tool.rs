@@ -1,17 +1,45 @@
 use std::path::PathBuf;
 use async_std::fs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::module::api::{source_data::SourceDataModule, folder::{FolderModule, FolderCreateForm}};
 
-use super::Context;
+use super::{Context, CommandError, CommandResult};
 
-pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tree: &Option<PathBuf>, dry_run: bool) {
+#[derive(Serialize)]
+struct DirectoryItem {
+    directory: String,
+    status: DirectoryStatus,
+    success: usize,
+    missing: usize,
+    error: usize,
+    message: Option<String>
+}
+
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum DirectoryStatus {
+    Completed,
+    Empty,
+    ReadFailed,
+    AnalyseFailed,
+    CreateFolderFailed
+}
+
+#[derive(Serialize)]
+struct ImportFolderSummary {
+    directories: usize,
+    completed: usize,
+    failed: usize
+}
+
+pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tree: &Option<PathBuf>, dry_run: bool) -> CommandResult {
     if let Err(e) = context.server_manager.waiting_for_start().await {
-        eprintln!("Cannot establish connection to server. {}", e);
-        return
+        context.output.error("Cannot establish connection to server.", e);
+        return Result::Err(CommandError::Connection)
     }
 
+    let mut items: Vec<DirectoryItem> = Vec::new();
     let mut source_data_module = SourceDataModule::new(context.server_manager);
     let mut folder_module = FolderModule::new(context.server_manager);
 
@@ -19,8 +47,8 @@ pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tr
         let root = match std::fs::read_dir(file) {
             Ok(ok) => ok,
             Err(e) => {
-                eprintln!("File input read error. {}", e);
-                return
+                context.output.error("File input read error.", e);
+                return Result::Err(CommandError::Input)
             }
         };
         let mut dirs: Vec<_> = root.filter(|i| i.as_ref().is_ok_and(|f| f.file_type().is_ok_and(|f| f.is_dir())))
@@ -36,7 +64,9 @@ pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tr
             let dir = match std::fs::read_dir(&dir_path) {
                 Ok(ok) => ok,
                 Err(e) => {
-                    println!("{:50}| Read failed. \x1b[1;31m{}\x1b[0m", dir_name, e);
+                    let item = DirectoryItem::new(dir_name, DirectoryStatus::ReadFailed, Option::Some(e.to_string()));
+                    context.output.item(&item, print_directory_item);
+                    items.push(item);
                     continue
                 }
             };
@@ -49,25 +79,29 @@ pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tr
             let filenames: Vec<_> = filenames.iter().map(|f| f.as_str()).collect();
     
             if filenames.is_empty() {
-                println!("{:50}| Empty.", dir_name);
+                let item = DirectoryItem::new(dir_name, DirectoryStatus::Empty, Option::None);
+                context.output.item(&item, print_directory_item);
+                items.push(item);
                 continue
             }
-            
-            process_one_direcory(&mut source_data_module, &mut folder_module, dry_run, dir_name, filenames).await
+
+            let item = process_one_direcory(&mut source_data_module, &mut folder_module, dry_run, dir_name, filenames).await;
+            context.output.item(&item, print_directory_item);
+            items.push(item);
         }
     }else if let Some(tree) = tree {
         let f = match fs::read(tree).await {
             Ok(o) => o,
             Err(e) => {
-                eprintln!("Tree input read error. {}", e);
-                return
+                context.output.error("Tree input read error.", e);
+                return Result::Err(CommandError::Input)
             }
         };
         let json: Vec<TreeJsonNode> = match serde_json::from_slice(&f) {
             Ok(o) => o,
             Err(e) => {
-                eprintln!("Tree json parse error, {}", e);
-                return
+                context.output.error("Tree json parse error,", e);
+                return Result::Err(CommandError::Input)
             }
         };
         for node in json {
@@ -80,22 +114,34 @@ pub async fn import_folder(context: &mut Context<'_>, file: &Option<PathBuf>, tr
                         _ => Option::None
                     }).collect()
                 }else{
-                    println!("{:50}| Empty.", dir_name);
+                    let item = DirectoryItem::new(&dir_name, DirectoryStatus::Empty, Option::None);
+                    context.output.item(&item, print_directory_item);
+                    items.push(item);
                     continue
                 };
                 let filenames: Vec<_> = filenames.iter().map(|f| f.as_str()).collect();
 
                 if filenames.is_empty() {
-                    println!("{:50}| Empty.", dir_name);
+                    let item = DirectoryItem::new(&dir_name, DirectoryStatus::Empty, Option::None);
+                    context.output.item(&item, print_directory_item);
+                    items.push(item);
                     continue
                 }
 
-                process_one_direcory(&mut source_data_module, &mut folder_module, dry_run, &dir_name, filenames).await
+                let item = process_one_direcory(&mut source_data_module, &mut folder_module, dry_run, &dir_name, filenames).await;
+                context.output.item(&item, print_directory_item);
+                items.push(item);
             }
         }
     }else{
-        panic!("Neither file nor tree is specified.")
+        context.output.error("Neither file nor tree is specified.", "");
+        return Result::Err(CommandError::Input)
     }
+
+    let completed = items.iter().filter(|i| i.status == DirectoryStatus::Completed).count();
+    let failed = items.iter().filter(|i| i.status != DirectoryStatus::Completed && i.status != DirectoryStatus::Empty).count();
+    context.output.summary(&items, &ImportFolderSummary { directories: items.len(), completed, failed }, |_| {});
+    CommandError::of_failed(failed)
 }
 
 async fn write_into_folder(folder_module: &mut FolderModule<'_>, folder_name: &str, image_ids: Vec<i32>) -> Result<i32, Box<dyn std::error::Error>> {
@@ -105,45 +151,55 @@ async fn write_into_folder(folder_module: &mut FolderModule<'_>, folder_name: &s
     }
 }
 
-async fn process_one_direcory(source_data_module: &mut SourceDataModule<'_>, folder_module: &mut FolderModule<'_>, dry_run: bool, dir_name: &str, filenames: Vec<&str>) {
+async fn process_one_direcory(source_data_module: &mut SourceDataModule<'_>, folder_module: &mut FolderModule<'_>, dry_run: bool, dir_name: &str, filenames: Vec<&str>) -> DirectoryItem {
     let result = match source_data_module.analyse_source_name(&filenames).await {
-        Err(e) => {
-            eprintln!("{:50}| Error occrred in analyse source name request. {}", dir_name, e.to_string());
-            return
-        },
+        Err(e) => return DirectoryItem::new(dir_name, DirectoryStatus::AnalyseFailed, Option::Some(e.to_string())),
         Ok(r) => r
     };
 
     if !dry_run {
         let image_ids: Vec<_> = result.iter().filter(|i| i.image_id.is_some()).map(|i| i.image_id.unwrap()).collect();
 
-        match write_into_folder(folder_module, dir_name, image_ids).await {
-            Err(e) => {
-                eprintln!("{:50}| Error occrred in create folder request. {}", dir_name, e.to_string());
-                return
-            },
-            Ok(_) => {}
+        if let Err(e) = write_into_folder(folder_module, dir_name, image_ids).await {
+            return DirectoryItem::new(dir_name, DirectoryStatus::CreateFolderFailed, Option::Some(e.to_string()))
         }
     }
 
-    let mut success = 0;
-    let mut missing = 0;
-    let mut error = 0;
+    let mut item = DirectoryItem::new(dir_name, DirectoryStatus::Completed, Option::None);
     for r in result {
         if r.error.is_some() {
-            error += 1;
+            item.error += 1;
         }else if r.image_id.is_some() {
-            success += 1;
+            item.success += 1;
         }else{
-            missing += 1;
+            item.missing += 1;
         }
     }
+    item
+}
 
-    print!("{:50}| ", dir_name);
-    if success > 0 { print!("success \x1b[1;32m{}\x1b[0m, ", success) }else{ print!("success 0, ") }
-    if missing > 0 { print!("missing \x1b[1;33m{}\x1b[0m, ", missing) }else{ print!("missing 0, ") }
-    if error > 0 { print!("error \x1b[1;31m{}\x1b[0m.", error) }else{ print!("error 0.") }
-    println!()
+impl DirectoryItem {
+    fn new(directory: &str, status: DirectoryStatus, message: Option<String>) -> DirectoryItem {
+        DirectoryItem { directory: directory.to_string(), status, success: 0, missing: 0, error: 0, message }
+    }
+}
+
+fn print_directory_item(item: &DirectoryItem) {
+    let dir_name = &item.directory;
+    let message = item.message.as_deref().unwrap_or("");
+    match item.status {
+        DirectoryStatus::Empty => println!("{:50}| Empty.", dir_name),
+        DirectoryStatus::ReadFailed => println!("{:50}| Read failed. \x1b[1;31m{}\x1b[0m", dir_name, message),
+        DirectoryStatus::AnalyseFailed => eprintln!("{:50}| Error occrred in analyse source name request. {}", dir_name, message),
+        DirectoryStatus::CreateFolderFailed => eprintln!("{:50}| Error occrred in create folder request. {}", dir_name, message),
+        DirectoryStatus::Completed => {
+            print!("{:50}| ", dir_name);
+            if item.success > 0 { print!("success \x1b[1;32m{}\x1b[0m, ", item.success) }else{ print!("success 0, ") }
+            if item.missing > 0 { print!("missing \x1b[1;33m{}\x1b[0m, ", item.missing) }else{ print!("missing 0, ") }
+            if item.error > 0 { print!("error \x1b[1;31m{}\x1b[0m.", item.error) }else{ print!("error 0.") }
+            println!()
+        }
+    }
 }
 
 #[derive(Deserialize)]
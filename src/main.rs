@@ -3,75 +3,184 @@ mod command;
 mod module;
 mod utils;
 
+use std::io::Write;
+use std::sync::Arc;
 use clap::{Parser, CommandFactory};
 use clap_complete::generate;
-use cli::{Cli, Import, Channel, Server, SourceData, Tool};
-use command::apply::ApplyInputType;
+use cli::{Cli, Commands, Import, Channel, Server, SourceData, Tool, ApplyCommand, ApplySource};
+use command::CommandError;
+use command::apply::{ApplyInputType, ApplyOptions, ChunkOptions};
+use command::export::ExportParts;
 use module::local_data::LocalDataManager;
 use module::channel::ChannelManager;
 use module::server::ServerManager;
+use module::trace::{Tracer, TraceTarget};
+use utils::error::HedgeError;
+use utils::output::Output;
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
-    let config = module::config::load_config();
+    let output = Output::new(cli.output);
+    let config = match module::config::load_config() {
+        Ok(c) => c,
+        Err(e) => exit_with(&output, "Cannot load config.", &e)
+    };
     let local_data_manager = LocalDataManager::new(&config);
-    let channel_manager = ChannelManager::new(&config, &local_data_manager);
-    let mut server_manager = ServerManager::new(&config, &channel_manager);
+    // --channel优先于HEDGE_CHANNEL，二者都只影响本次调用
+    let channel_override = cli.channel.clone().or_else(|| std::env::var("HEDGE_CHANNEL").ok().filter(|c| !c.is_empty()));
+    let channel_manager = match ChannelManager::new(&config, &local_data_manager, channel_override) {
+        Ok(c) => c,
+        Err(e) => exit_with(&output, "Cannot load cli context.", &e)
+    };
+    let verbose = verbose_level(&cli);
+    let tracer = match trace_target(&cli, verbose) {
+        Ok(Some(target)) => match Tracer::new(&config, target) {
+            Ok(t) => Option::Some(Arc::new(t)),
+            Err(e) => exit_with(&output, "Cannot enable request tracing.", &e)
+        },
+        Ok(None) => Option::None,
+        Err(e) => exit_with(&output, "Cannot enable request tracing.", &e)
+    };
+    let mut server_manager = match ServerManager::new(&config, &channel_manager) {
+        Ok(s) => s,
+        Err(e) => exit_with(&output, "Cannot prepare server connection.", &e)
+    };
+    if let Some(tracer) = &tracer {
+        server_manager.add_middleware(tracer.clone());
+    }
     let mut context = command::Context {
         config,
         local_data_manager: &local_data_manager,
         channel_manager: &channel_manager,
-        server_manager: &mut server_manager
+        server_manager: &mut server_manager,
+        output,
+        tracer
     };
-    match cli {
-        Cli::App => command::app::start_app(&context),
-        Cli::Channel(channel) => match channel {
-            Channel::Info => command::channel::info(&context),
-            Channel::Use { channel_name } => command::channel::use_channel(&context, channel_name)
+    let result = match cli.command {
+        Commands::App => command::app::start_app(&context),
+        Commands::Channel(channel) => match channel {
+            Channel::Info => command::channel::info(&context).await,
+            Channel::Show { channel_name } => command::channel::show(&context, &channel_name).await,
+            Channel::Use { channel_name } => command::channel::use_channel(&context, channel_name),
+            Channel::Create { channel_name } => command::channel::create(&context, &channel_name),
+            Channel::Remove { channel_name, yes } => command::channel::remove(&context, &channel_name, yes),
+            Channel::Rename { channel_name, new_name } => command::channel::rename(&context, &channel_name, &new_name),
+            Channel::Copy { channel_name, new_name } => command::channel::copy(&context, &channel_name, &new_name),
+            Channel::Login { host, token, credentials_file } => command::channel::login(&mut context, &host, token, credentials_file).await,
+            Channel::Logout => command::channel::logout(&context),
+            Channel::Whoami => command::channel::whoami(&mut context).await
         }
-        Cli::Server(server) => match server {
+        Commands::Server(server) => match server {
             Server::Status => command::server::status(&mut context).await,
-            Server::Start => command::server::start(&mut context).await,
-            Server::Stop => command::server::stop(&mut context).await,
+            Server::Start { no_spawn } => command::server::start(&mut context, no_spawn).await,
+            Server::Stop { now, timeout } => command::server::stop(&mut context, now, timeout).await,
+            Server::Supervise { interval, max_backoff, unhealthy_timeout } => command::server::supervise(&mut context, interval, max_backoff, unhealthy_timeout).await,
+            Server::InstallService { install, enable, timer_command, timer_calendar } => command::server::install_service(&context, install, enable, timer_command, timer_calendar),
+            Server::RotateLog => command::server::rotate_log(&context),
+            Server::Restart { timeout } => command::server::restart(&mut context, timeout).await,
             Server::Kill => command::server::kill(&mut context).await,
-            Server::Log => command::server::log(&context)
+            Server::Log { follow, lines, since, grep, level } => command::server::log(&context, follow, lines, since, grep, level).await
         }
-        Cli::Apply(apply) => {
-            let mut input: Vec<ApplyInputType> = Vec::new();
-            if let Some(f) = apply.directory {
-                for ele in f {
-                    input.push(ApplyInputType::Directory(ele));
+        Commands::Apply(apply) => match apply.command {
+            Some(ApplyCommand::Validate { source, schema }) => if schema {
+                command::apply::validate::print_schema()
+            }else{
+                let input = apply_input_of(&source);
+                if input.is_empty() {
+                    context.output.error("Options --directory, --file and --input should have least one.", "");
+                    Result::Err(CommandError::Input)
+                }else{
+                    command::apply::validate::validate(&context.output, &input, &source.glob, &source.vars, source.merge)
                 }
             }
-            if let Some(f) = apply.file {
-                for ele in f {
-                    input.push(ApplyInputType::File(ele));
+            None => {
+                let input = apply_input_of(&apply.source);
+                if input.is_empty() {
+                    context.output.error("Options --directory, --file and --input should have least one.", "");
+                    Result::Err(CommandError::Input)
+                }else{
+                    let options = ApplyOptions { verbose: verbose > 0, dry_run: apply.dry_run, prune: apply.prune, yes: apply.yes, globs: apply.source.glob, vars: apply.source.vars, merge: apply.source.merge, chunk: ChunkOptions { chunk_size: apply.chunk_size, retry: apply.retry, resume: !apply.no_resume } };
+                    command::apply::apply(&mut context, &input, &options).await
                 }
             }
-            if apply.input {
-                input.push(ApplyInputType::Input);
-            }
-            if input.is_empty() {
-                eprintln!("Options --directory, --file and --input should have least one.")
-            }else{
-                command::apply::apply(&mut context, &input, apply.verbose).await
-            }
         }
-        Cli::Import(import) => match import {
+        Commands::Export(export) => {
+            let all = !export.tags && !export.topics && !export.authors && !export.setting;
+            let parts = ExportParts { tags: all || export.tags, topics: all || export.topics, authors: all || export.authors, setting: all || export.setting };
+            command::export::export(&mut context, &export.file, export.format, &parts).await
+        }
+        Commands::Import(import) => match import {
             Import::Add { files, remove } => command::import::add(&mut context, &files, remove).await,
             Import::Batch { partition_time, create_time, order_time, analyse_source } => command::import::batch(&mut context, partition_time, create_time, order_time, analyse_source).await,
             Import::List => command::import::list(&mut context).await,
             Import::Save => command::import::save(&mut context).await
         }
-        Cli::SourceData(source_data) => match source_data {
+        Commands::SourceData(source_data) => match source_data {
             SourceData::Query { hql, limit, offset } => command::source_data::query(&mut context, hql.as_str(), offset, limit).await,
             SourceData::Download => command::source_data::download(&mut context).await,
-            SourceData::Connect { split, limit, update, verbose } => command::source_data::connect(&mut context, &split, limit, update, verbose).await
+            SourceData::Connect { split, limit, update, .. } => command::source_data::connect(&mut context, &split, limit, update, verbose > 0).await
         }
-        Cli::Tool(tool) => match tool {
+        Commands::Tool(tool) => match tool {
             Tool::ImportFolder { dir, tree, dry_run } => command::tool::import_folder(&mut context, &dir, &tree, dry_run).await
         }
-        Cli::Completion(completion) => generate(completion.shell, &mut Cli::command(), "hedge", &mut std::io::stdout())
+        Commands::Completion(completion) => {
+            generate(completion.shell, &mut Cli::command(), "hedge", &mut std::io::stdout());
+            Result::Ok(())
+        }
+    };
+    if let Err(e) = result {
+        let _ = std::io::stdout().flush();
+        std::process::exit(e.exit_code())
+    }
+}
+
+// 启动阶段的错误，输出后直接退出。格式错误视为输入错误
+fn exit_with(output: &Output, message: &str, e: &HedgeError) -> ! {
+    output.failure(message, e);
+    let code = if e.is_format() { CommandError::Input }else{ CommandError::Failed };
+    std::process::exit(code.exit_code())
+}
+
+// apply与source-data connect保留了各自的-v/--verbose，与全局的-v取较大者
+fn verbose_level(cli: &Cli) -> u8 {
+    let command_verbose = match &cli.command {
+        Commands::Apply(apply) => apply.verbose,
+        Commands::SourceData(SourceData::Connect { verbose, .. }) => *verbose,
+        _ => 0
+    };
+    cli.verbose.max(command_verbose)
+}
+
+// --trace或-vv输出到stderr；HEDGE_CLI_LOG可单独开启追踪，并指定输出位置
+fn trace_target(cli: &Cli, verbose: u8) -> Result<Option<TraceTarget>, HedgeError> {
+    let env_target = match std::env::var("HEDGE_CLI_LOG") {
+        Ok(v) => TraceTarget::parse(&v)?,
+        Err(_) => Option::None
+    };
+    if env_target.is_some() {
+        Result::Ok(env_target)
+    }else if cli.trace || verbose >= 2 {
+        Result::Ok(Option::Some(TraceTarget::Stderr))
+    }else{
+        Result::Ok(Option::None)
+    }
+}
+
+fn apply_input_of(source: &ApplySource) -> Vec<ApplyInputType> {
+    let mut input: Vec<ApplyInputType> = Vec::new();
+    if let Some(f) = &source.directory {
+        for ele in f {
+            input.push(ApplyInputType::Directory(ele.clone()));
+        }
+    }
+    if let Some(f) = &source.file {
+        for ele in f {
+            input.push(ApplyInputType::File(ele.clone()));
+        }
+    }
+    if source.input {
+        input.push(ApplyInputType::Input);
     }
-}
\ No newline at end of file
+    input
+}
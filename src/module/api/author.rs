@@ -0,0 +1,65 @@
+use std::error::Error;
+use reqwest::Method;
+use serde::Deserialize;
+use crate::module::server::ServerManager;
+use super::{bulk::AuthorBulkForm, source_data::MappingSourceTagDto, tag::AnnotationRes};
+
+
+pub struct AuthorModule<'t> {
+    server_manager: &'t ServerManager
+}
+
+impl <'t> AuthorModule<'t> {
+    pub fn new(server_manager: &'t ServerManager) -> AuthorModule<'t> {
+        AuthorModule { server_manager }
+    }
+    pub async fn list_all(&mut self) -> Result<Vec<AuthorRes>, Box<dyn Error>> {
+        self.server_manager.list_all("/api/authors").await
+    }
+    pub async fn get(&self, author_id: i32) -> Result<AuthorDetailRes, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, &format!("/api/authors/{author_id}")).fetch().await
+    }
+    pub async fn delete(&mut self, author_id: i32) -> Result<(), Box<dyn Error>> {
+        self.server_manager.request(Method::DELETE, &format!("/api/authors/{author_id}")).execute().await
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthorRes {
+    pub id: i32,
+    pub name: String
+}
+
+#[derive(Deserialize)]
+pub struct AuthorDetailRes {
+    pub name: String,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    #[serde(rename = "type")]
+    pub author_type: String,
+    pub keywords: Vec<String>,
+    pub description: String,
+    pub favorite: bool,
+    pub score: Option<i32>,
+    #[serde(default)]
+    pub annotations: Vec<AnnotationRes>,
+    #[serde(rename = "mappingSourceTags", default)]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+impl AuthorDetailRes {
+    pub fn to_bulk_form(&self) -> AuthorBulkForm {
+        AuthorBulkForm {
+            name: self.name.clone(),
+            rename: Option::None,
+            other_names: if self.other_names.is_empty() { Option::None }else{ Option::Some(self.other_names.clone()) },
+            tag_type: Option::Some(self.author_type.clone()),
+            keywords: if self.keywords.is_empty() { Option::None }else{ Option::Some(self.keywords.clone()) },
+            description: if self.description.is_empty() { Option::None }else{ Option::Some(self.description.clone()) },
+            annotations: if self.annotations.is_empty() { Option::None }else{ Option::Some(self.annotations.iter().map(|a| a.name.clone()).collect()) },
+            favorite: Option::Some(self.favorite),
+            score: self.score,
+            mapping_source_tags: if self.mapping_source_tags.is_empty() { Option::None }else{ Option::Some(self.mapping_source_tags.iter().map(|m| m.to_form()).collect()) }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::error::Error;
 use reqwest::Method;
 use serde::{Serialize, Deserialize};
+use schemars::JsonSchema;
 use super::super::server::{ServerManager, ErrorResult};
 
 
@@ -8,25 +9,26 @@ pub struct BulkModule<'t> {
     server_manager: &'t ServerManager
 }
 
+// bulk请求由apply分块提交，失败的块由分块逻辑重试。POST请求本身默认不重试，因此重试不会叠加
 impl <'t> BulkModule<'t> {
     pub fn new(server_manager: &'t ServerManager) -> BulkModule {
         BulkModule { server_manager }        
     }
-    pub async fn source_data_bulk_update(&mut self, bulks: &Vec<SourceDataBulkForm>) -> Result<BulkResult<SourceDataIdentity>, Box<dyn Error>> {
+    pub async fn source_data_bulk_update(&mut self, bulks: &[SourceDataBulkForm]) -> Result<BulkResult<SourceDataIdentity>, Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_with_body(Method::POST, "/api/source-data/bulk", body).await
+        self.server_manager.request(Method::POST, "/api/source-data/bulk").body(body).fetch().await
     }
-    pub async fn tag_bulk_update(&mut self, bulks: &Vec<TagBulkForm>) -> Result<BulkResult<String>, Box<dyn Error>> {
+    pub async fn tag_bulk_update(&mut self, bulks: &[TagBulkForm]) -> Result<BulkResult<String>, Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_with_body(Method::POST, "/api/tags/bulk", body).await
+        self.server_manager.request(Method::POST, "/api/tags/bulk").body(body).fetch().await
     }
-    pub async fn topic_bulk_update(&mut self, bulks: &Vec<TopicBulkForm>) -> Result<BulkResult<String>, Box<dyn Error>> {
+    pub async fn topic_bulk_update(&mut self, bulks: &[TopicBulkForm]) -> Result<BulkResult<String>, Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_with_body(Method::POST, "/api/topics/bulk", body).await
+        self.server_manager.request(Method::POST, "/api/topics/bulk").body(body).fetch().await
     }
-    pub async fn author_bulk_update(&mut self, bulks: &Vec<AuthorBulkForm>) -> Result<BulkResult<String>, Box<dyn Error>> {
+    pub async fn author_bulk_update(&mut self, bulks: &[AuthorBulkForm]) -> Result<BulkResult<String>, Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_with_body(Method::POST, "/api/authors/bulk", body).await
+        self.server_manager.request(Method::POST, "/api/authors/bulk").body(body).fetch().await
     }
 }
 
@@ -51,7 +53,7 @@ pub struct SourceDataIdentity {
     pub source_id: i64
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct TagBulkForm {
     pub name: String,
@@ -77,7 +79,7 @@ pub struct TagBulkForm {
     pub children: Option<Vec<Box<TagBulkForm>>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct TopicBulkForm {
     pub name: String,
@@ -103,7 +105,7 @@ pub struct TopicBulkForm {
     pub children: Option<Vec<Box<TopicBulkForm>>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AuthorBulkForm {
     pub name: String,
@@ -127,7 +129,7 @@ pub struct AuthorBulkForm {
     pub mapping_source_tags: Option<Vec<MappingSourceTagForm>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceDataBulkForm {
     #[serde(rename = "sourceSite", alias = "source_site", alias = "site")]
@@ -152,7 +154,7 @@ pub struct SourceDataBulkForm {
     pub additional_info: Option<Vec<AdditionalInfoForm>>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceTagForm {
     pub code: String,
@@ -164,7 +166,7 @@ pub struct SourceTagForm {
     pub tag_type: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceBookForm {
     pub code: String,
@@ -174,14 +176,14 @@ pub struct SourceBookForm {
     pub other_title: Option<String>
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AdditionalInfoForm {
     pub field: String,
     pub value: String
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct MappingSourceTagForm {
     pub site: String,
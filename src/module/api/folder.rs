@@ -14,15 +14,15 @@ impl <'t> FolderModule<'t> {
     }
     pub async fn create(&mut self, form: &FolderCreateForm) -> Result<IdRes, Box<dyn Error>> {
         let body = serde_json::to_value(form)?;
-        self.server_manager.req_with_body(Method::POST, format!("/api/folders"), body).await
+        self.server_manager.request(Method::POST, "/api/folders").body(body).fetch().await
     }
     pub async fn _update(&mut self, folder_id: i32, form: &FolderUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(form)?;
-        self.server_manager.req_without_res(Method::PATCH, format!("/api/folders/{folder_id}"), body).await
+        self.server_manager.request(Method::PATCH, &format!("/api/folders/{folder_id}")).body(body).execute().await
     }
     pub async fn _partial_update_images(&mut self, folder_id: i32, form: &FolderImagesPartialUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(form)?;
-        self.server_manager.req_without_res(Method::PATCH, format!("/api/folders/{folder_id}/images"), body).await
+        self.server_manager.request(Method::PATCH, &format!("/api/folders/{folder_id}/images")).body(body).execute().await
     }
 }
 
@@ -1,4 +1,7 @@
 pub mod source_data;
 pub mod bulk;
 pub mod setting;
-pub mod folder;
\ No newline at end of file
+pub mod folder;
+pub mod tag;
+pub mod topic;
+pub mod author;
@@ -1,6 +1,7 @@
 use std::{error::Error, collections::HashMap};
 use reqwest::Method;
 use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
 use crate::module::server::ServerManager;
 
 
@@ -12,47 +13,47 @@ impl <'t> SettingModule<'t> {
     pub fn new(server_manager: &'t ServerManager) -> SettingModule {
         SettingModule { server_manager }        
     }
-    pub async fn _get_storage_option(&mut self) -> Result<StorageOption, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/file").await
+    pub async fn get_storage_option(&mut self) -> Result<StorageOption, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/file").fetch().await
     }
-    pub async fn _get_find_similar_option(&mut self) -> Result<FindSimilarOption, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/find-similar").await
+    pub async fn get_find_similar_option(&mut self) -> Result<FindSimilarOption, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/find-similar").fetch().await
     }
-    pub async fn _get_query_option(&mut self) -> Result<QueryOption, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/query").await
+    pub async fn get_query_option(&mut self) -> Result<QueryOption, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/query").fetch().await
     }
-    pub async fn _get_meta_option(&mut self) -> Result<MetaOption, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/meta").await
+    pub async fn get_meta_option(&mut self) -> Result<MetaOption, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/meta").fetch().await
     }
-    pub async fn _get_import_option(&mut self) -> Result<ImportOption, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/import").await
+    pub async fn get_import_option(&mut self) -> Result<ImportOption, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/import").fetch().await
     }
-    pub async fn _get_source_sites(&mut self) -> Result<Vec<SourceSite>, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/setting/source/sites").await
+    pub async fn get_source_sites(&mut self) -> Result<Vec<SourceSite>, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/setting/source/sites").fetch().await
     }
     pub async fn set_storage_option(&mut self, bulks: &StorageOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PATCH, "/api/setting/file", body).await
+        self.server_manager.request(Method::PATCH, "/api/setting/file").body(body).retry().execute().await
     }
     pub async fn set_find_similar_option(&mut self, bulks: &FindSimilarOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PATCH, "/api/setting/find-similar", body).await
+        self.server_manager.request(Method::PATCH, "/api/setting/find-similar").body(body).retry().execute().await
     }
     pub async fn set_query_option(&mut self, bulks: &QueryOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PATCH, "/api/setting/query", body).await
+        self.server_manager.request(Method::PATCH, "/api/setting/query").body(body).retry().execute().await
     }
     pub async fn set_meta_option(&mut self, bulks: &MetaOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PATCH, "/api/setting/meta", body).await
+        self.server_manager.request(Method::PATCH, "/api/setting/meta").body(body).retry().execute().await
     }
     pub async fn set_import_option(&mut self, bulks: &ImportOptionUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PATCH, "/api/setting/import", body).await
+        self.server_manager.request(Method::PATCH, "/api/setting/import").body(body).retry().execute().await
     }
     pub async fn set_source_sites(&mut self, bulks: &Vec<SourceSiteUpdateForm>) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(bulks)?;
-        self.server_manager.req_without_res(Method::PUT, "/api/setting/source/sites", body).await
+        self.server_manager.request(Method::PUT, "/api/setting/source/sites").body(body).execute().await
     }
 }
 
@@ -146,7 +147,7 @@ pub struct SourceSite {
     pub available_types: Vec<String>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct StorageOptionUpdateForm {
     #[serde(rename = "storagePath")]
@@ -165,7 +166,7 @@ pub struct StorageOptionUpdateForm {
     pub block_max_count: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarOptionUpdateForm {
     #[serde(rename = "autoFindSimilar", alias = "auto_find_similar", skip_serializing_if = "Option::is_none")]
@@ -176,7 +177,7 @@ pub struct FindSimilarOptionUpdateForm {
     pub default_task_conf: Option<FindSimilarTaskConfig>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct QueryOptionUpdateForm {
     #[serde(rename = "chineseSymbolReflect", alias = "chinese_symbol_reflect", skip_serializing_if = "Option::is_none")]
@@ -191,7 +192,7 @@ pub struct QueryOptionUpdateForm {
     pub warning_limit_of_intersect_items: Option<i32>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct MetaOptionUpdateForm {
     #[serde(rename = "autoCleanTagme", alias = "auto_clean_tagme", skip_serializing_if = "Option::is_none")]
@@ -202,7 +203,7 @@ pub struct MetaOptionUpdateForm {
     pub author_colors: Option<HashMap<String, String>>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct ImportOptionUpdateForm {
     #[serde(rename = "autoAnalyseSourceData", alias = "auto_analyse_source_data", skip_serializing_if = "Option::is_none")]
@@ -227,7 +228,7 @@ pub struct ImportOptionUpdateForm {
     pub watch_path_initialize: Option<bool>
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceSiteUpdateForm {
     pub name: String,
@@ -243,7 +244,7 @@ pub struct SourceSiteUpdateForm {
     pub available_types: Option<Vec<String>>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarTaskConfig {
     #[serde(rename = "findBySourceIdentity", alias = "find_by_source_identity")]
@@ -266,7 +267,7 @@ pub struct FindSimilarTaskConfig {
     pub filter_by_source_tag_type: Vec<FindSimilarTaskConfigSourceTagType>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct FindSimilarTaskConfigSourceTagType {
     #[serde(rename = "sourceSite", alias = "source_site")]
@@ -275,7 +276,7 @@ pub struct FindSimilarTaskConfigSourceTagType {
     pub tag_type: String
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceAnalyseRule {
     pub site: String,
@@ -289,7 +290,7 @@ pub struct SourceAnalyseRule {
     pub extras: Option<Vec<SourceAnalyseRuleExtra>>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SourceAnalyseRuleExtra {
     pub group: String,
@@ -303,9 +304,85 @@ pub struct SourceAnalyseRuleExtra {
     pub translate_underscore_to_space: Option<bool>
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct AvailableAdditionalInfo {
     pub field: String,
     pub label: String
 }
+
+impl StorageOption {
+    pub fn to_update_form(&self) -> StorageOptionUpdateForm {
+        StorageOptionUpdateForm {
+            storage_path: self.storage_path.clone(),
+            auto_clean_trashes: Option::Some(self.auto_clean_trashes),
+            auto_clean_trashes_interval_day: Option::Some(self.auto_clean_trashes_interval_day),
+            auto_clean_caches: Option::Some(self.auto_clean_caches),
+            auto_clean_caches_interval_day: Option::Some(self.auto_clean_caches_interval_day),
+            block_max_size: Option::Some(self.block_max_size),
+            block_max_count: Option::Some(self.block_max_count)
+        }
+    }
+}
+
+impl FindSimilarOption {
+    pub fn to_update_form(&self) -> FindSimilarOptionUpdateForm {
+        FindSimilarOptionUpdateForm {
+            auto_find_similar: Option::Some(self.auto_find_similar),
+            auto_task_conf: self.auto_task_conf.clone(),
+            default_task_conf: Option::Some(self.default_task_conf.clone())
+        }
+    }
+}
+
+impl QueryOption {
+    pub fn to_update_form(&self) -> QueryOptionUpdateForm {
+        QueryOptionUpdateForm {
+            chinese_symbol_reflect: Option::Some(self.chinese_symbol_reflect),
+            translate_underscore_to_space: Option::Some(self.translate_underscore_to_space),
+            query_limit_of_query_items: Option::Some(self.query_limit_of_query_items),
+            warning_limit_of_union_items: Option::Some(self.warning_limit_of_union_items),
+            warning_limit_of_intersect_items: Option::Some(self.warning_limit_of_intersect_items)
+        }
+    }
+}
+
+impl MetaOption {
+    pub fn to_update_form(&self) -> MetaOptionUpdateForm {
+        MetaOptionUpdateForm {
+            auto_clean_tagme: Option::Some(self.auto_clean_tagme),
+            topic_colors: Option::Some(self.topic_colors.clone()),
+            author_colors: Option::Some(self.author_colors.clone())
+        }
+    }
+}
+
+impl ImportOption {
+    pub fn to_update_form(&self) -> ImportOptionUpdateForm {
+        ImportOptionUpdateForm {
+            auto_analyse_source_data: Option::Some(self.auto_analyse_source_data),
+            set_tagme_of_tag: Option::Some(self.set_tagme_of_tag),
+            set_tagme_of_source: Option::Some(self.set_tagme_of_source),
+            set_order_time_by: Option::Some(self.set_order_time_by.clone()),
+            set_partition_time_delay_hour: Option::Some(self.set_partition_time_delay_hour),
+            source_analyse_rules: Option::Some(self.source_analyse_rules.clone()),
+            watch_paths: Option::Some(self.watch_paths.clone()),
+            auto_watch_path: Option::Some(self.auto_watch_path),
+            watch_path_move_file: Option::Some(self.watch_path_move_file),
+            watch_path_initialize: Option::Some(self.watch_path_initialize)
+        }
+    }
+}
+
+impl SourceSite {
+    pub fn to_update_form(&self) -> SourceSiteUpdateForm {
+        SourceSiteUpdateForm {
+            name: self.name.clone(),
+            title: Option::Some(self.title.clone()),
+            part_mode: Option::Some(self.part_mode.clone()),
+            available_additional_info: Option::Some(self.available_additional_info.clone()),
+            source_link_generate_rules: Option::Some(self.source_link_generate_rules.clone()),
+            available_types: Option::Some(self.available_types.clone())
+        }
+    }
+}
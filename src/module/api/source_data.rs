@@ -3,6 +3,7 @@ use reqwest::Method;
 use serde::{Serialize, Deserialize};
 use serde_json::json;
 use crate::module::import::SourceDataPath;
+use super::bulk::{self, MappingSourceTagForm};
 use crate::module::server::{ServerManager, ListResult};
 
 
@@ -21,24 +22,24 @@ impl <'t> SourceDataModule<'t> {
         if let Some(site) = site { query.push(("site", site.join(","))) }
         if let Some(limit) = limit { query.push(("limit", limit.to_string())) }
         if let Some(offset) = offset { query.push(("offset", offset.to_string())) }
-        self.server_manager.req_with_query(Method::GET, "/api/source-data", &query).await
+        self.server_manager.request(Method::GET, "/api/source-data").query(&query).fetch().await
     }
-    pub async fn get(&mut self, source_site: &str, source_id: i64) -> Result<SourceDataDetailRes, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, format!("/api/source-data/{source_site}/{source_id}")).await
+    pub async fn get(&self, source_site: &str, source_id: i64) -> Result<SourceDataDetailRes, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, &format!("/api/source-data/{source_site}/{source_id}")).fetch().await
     }
     pub async fn create(&mut self, source_site: &str, source_id: i64, form: &SourceDataUpdateForm) -> Result<(), Box<dyn Error>> {
         let mut body = serde_json::to_value(form)?;
         body["sourceSite"] = json!(source_site);
         body["sourceId"] = json!(source_id);
-        self.server_manager.req_without_res(Method::POST, format!("/api/source-data"), body).await
+        self.server_manager.request(Method::POST, "/api/source-data").body(body).execute().await
     }
     pub async fn update(&mut self, source_site: &str, source_id: i64, form: &SourceDataUpdateForm) -> Result<(), Box<dyn Error>> {
         let body = serde_json::to_value(form)?;
-        self.server_manager.req_without_res(Method::PATCH, format!("/api/source-data/{source_site}/{source_id}"), body).await
+        self.server_manager.request(Method::PATCH, &format!("/api/source-data/{source_site}/{source_id}")).body(body).retry().execute().await
     }
     pub async fn analyse_source_name(&mut self, filenames: &Vec<&str>) -> Result<Vec<SourceDataAnalyseResult>, Box<dyn Error>> {
         let body = serde_json::to_value(filenames)?;
-        self.server_manager.req_with_body(Method::POST, format!("/api/source-data/analyse-name"), body).await
+        self.server_manager.request(Method::POST, "/api/source-data/analyse-name").body(body).fetch().await
     }
 }
 
@@ -107,6 +108,17 @@ pub struct SourceTagDto {
     pub tag_type: Option<String>
 }
 
+#[derive(Deserialize)]
+pub struct MappingSourceTagDto {
+    pub site: String,
+    pub code: String,
+    pub name: String,
+    #[serde(rename = "otherName")]
+    pub other_name: Option<String>,
+    #[serde(rename = "type")]
+    pub tag_type: Option<String>
+}
+
 #[derive(Deserialize)]
 pub struct SourceBookDto {
     pub code: String,
@@ -167,3 +179,32 @@ pub struct AdditionalInfoForm {
     pub field: String,
     pub value: String
 }
+
+impl MappingSourceTagDto {
+    pub fn to_form(&self) -> MappingSourceTagForm {
+        MappingSourceTagForm {
+            site: self.site.clone(),
+            code: self.code.clone(),
+            name: if self.name.is_empty() { Option::None }else{ Option::Some(self.name.clone()) },
+            other_name: self.other_name.clone(),
+            tag_type: self.tag_type.clone()
+        }
+    }
+}
+
+impl SourceDataDetailRes {
+    pub fn to_bulk_form(&self) -> bulk::SourceDataBulkForm {
+        bulk::SourceDataBulkForm {
+            source_site: self.site.clone(),
+            source_id: self.source_id,
+            status: Option::Some(self.status.clone()),
+            title: if self.title.is_empty() { Option::None }else{ Option::Some(self.title.clone()) },
+            description: if self.description.is_empty() { Option::None }else{ Option::Some(self.description.clone()) },
+            tags: Option::Some(self.tags.iter().map(|t| bulk::SourceTagForm { code: t.code.clone(), name: Option::Some(t.name.clone()), other_name: t.other_name.clone(), tag_type: t.tag_type.clone() }).collect()),
+            books: Option::Some(self.books.iter().map(|b| bulk::SourceBookForm { code: b.code.clone(), title: Option::Some(b.title.clone()), other_title: b.other_title.clone() }).collect()),
+            relations: Option::Some(self.relations.clone()),
+            links: Option::Some(self.links.clone()),
+            additional_info: Option::Some(self.additional_info.iter().map(|a| bulk::AdditionalInfoForm { field: a.field.clone(), value: a.value.clone() }).collect())
+        }
+    }
+}
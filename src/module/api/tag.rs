@@ -0,0 +1,78 @@
+use std::error::Error;
+use reqwest::Method;
+use serde::Deserialize;
+use crate::module::server::ServerManager;
+use super::{bulk::TagBulkForm, source_data::MappingSourceTagDto};
+
+
+pub struct TagModule<'t> {
+    server_manager: &'t ServerManager
+}
+
+impl <'t> TagModule<'t> {
+    pub fn new(server_manager: &'t ServerManager) -> TagModule<'t> {
+        TagModule { server_manager }
+    }
+    pub async fn tree(&mut self) -> Result<Vec<TagTreeNode>, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, "/api/tags/tree").fetch().await
+    }
+    pub async fn get(&self, tag_id: i32) -> Result<TagDetailRes, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, &format!("/api/tags/{tag_id}")).fetch().await
+    }
+    pub async fn delete(&mut self, tag_id: i32) -> Result<(), Box<dyn Error>> {
+        self.server_manager.request(Method::DELETE, &format!("/api/tags/{tag_id}")).execute().await
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TagTreeNode {
+    pub id: i32,
+    pub name: String,
+    pub children: Option<Vec<TagTreeNode>>
+}
+
+#[derive(Deserialize)]
+pub struct TagDetailRes {
+    pub name: String,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    #[serde(rename = "type")]
+    pub tag_type: String,
+    pub group: String,
+    pub description: String,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub links: Vec<TagLinkRes>,
+    #[serde(default)]
+    pub annotations: Vec<AnnotationRes>,
+    #[serde(rename = "mappingSourceTags", default)]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+#[derive(Deserialize)]
+pub struct TagLinkRes {
+    pub name: String
+}
+
+#[derive(Deserialize)]
+pub struct AnnotationRes {
+    pub name: String
+}
+
+impl TagDetailRes {
+    pub fn to_bulk_form(&self, children: Option<Vec<TagBulkForm>>) -> TagBulkForm {
+        TagBulkForm {
+            name: self.name.clone(),
+            rename: Option::None,
+            other_names: if self.other_names.is_empty() { Option::None }else{ Option::Some(self.other_names.clone()) },
+            tag_type: Option::Some(self.tag_type.clone()),
+            group: Option::Some(self.group.clone()),
+            description: if self.description.is_empty() { Option::None }else{ Option::Some(self.description.clone()) },
+            links: if self.links.is_empty() { Option::None }else{ Option::Some(self.links.iter().map(|l| l.name.clone()).collect()) },
+            annotations: if self.annotations.is_empty() { Option::None }else{ Option::Some(self.annotations.iter().map(|a| a.name.clone()).collect()) },
+            color: self.color.clone(),
+            mapping_source_tags: if self.mapping_source_tags.is_empty() { Option::None }else{ Option::Some(self.mapping_source_tags.iter().map(|m| m.to_form()).collect()) },
+            children: children.map(|c| c.into_iter().map(Box::new).collect())
+        }
+    }
+}
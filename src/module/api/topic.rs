@@ -0,0 +1,69 @@
+use std::error::Error;
+use reqwest::Method;
+use serde::Deserialize;
+use crate::module::server::ServerManager;
+use super::{bulk::TopicBulkForm, source_data::MappingSourceTagDto, tag::AnnotationRes};
+
+
+pub struct TopicModule<'t> {
+    server_manager: &'t ServerManager
+}
+
+impl <'t> TopicModule<'t> {
+    pub fn new(server_manager: &'t ServerManager) -> TopicModule<'t> {
+        TopicModule { server_manager }
+    }
+    pub async fn list_all(&mut self) -> Result<Vec<TopicRes>, Box<dyn Error>> {
+        self.server_manager.list_all("/api/topics").await
+    }
+    pub async fn get(&self, topic_id: i32) -> Result<TopicDetailRes, Box<dyn Error>> {
+        self.server_manager.request(Method::GET, &format!("/api/topics/{topic_id}")).fetch().await
+    }
+    pub async fn delete(&mut self, topic_id: i32) -> Result<(), Box<dyn Error>> {
+        self.server_manager.request(Method::DELETE, &format!("/api/topics/{topic_id}")).execute().await
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TopicRes {
+    pub id: i32,
+    pub name: String
+}
+
+#[derive(Deserialize)]
+pub struct TopicDetailRes {
+    pub id: i32,
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<i32>,
+    pub name: String,
+    #[serde(rename = "otherNames")]
+    pub other_names: Vec<String>,
+    #[serde(rename = "type")]
+    pub topic_type: String,
+    pub keywords: Vec<String>,
+    pub description: String,
+    pub favorite: bool,
+    pub score: Option<i32>,
+    #[serde(default)]
+    pub annotations: Vec<AnnotationRes>,
+    #[serde(rename = "mappingSourceTags", default)]
+    pub mapping_source_tags: Vec<MappingSourceTagDto>
+}
+
+impl TopicDetailRes {
+    pub fn to_bulk_form(&self, children: Option<Vec<TopicBulkForm>>) -> TopicBulkForm {
+        TopicBulkForm {
+            name: self.name.clone(),
+            rename: Option::None,
+            other_names: if self.other_names.is_empty() { Option::None }else{ Option::Some(self.other_names.clone()) },
+            tag_type: Option::Some(self.topic_type.clone()),
+            keywords: if self.keywords.is_empty() { Option::None }else{ Option::Some(self.keywords.clone()) },
+            description: if self.description.is_empty() { Option::None }else{ Option::Some(self.description.clone()) },
+            annotations: if self.annotations.is_empty() { Option::None }else{ Option::Some(self.annotations.iter().map(|a| a.name.clone()).collect()) },
+            favorite: Option::Some(self.favorite),
+            score: self.score,
+            mapping_source_tags: if self.mapping_source_tags.is_empty() { Option::None }else{ Option::Some(self.mapping_source_tags.iter().map(|m| m.to_form()).collect()) },
+            children: children.map(|c| c.into_iter().map(Box::new).collect())
+        }
+    }
+}
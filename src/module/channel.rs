@@ -1,39 +1,198 @@
-use std::fs;
-use std::path::PathBuf;
+use std::{fs, io::ErrorKind};
+use std::path::{Path, PathBuf};
+use crate::utils::error::{HedgeError, ErrorContext};
 use super::config::LocalConfig;
+use super::credentials::CredentialsManager;
 use super::local_data::{LocalDataManager, LocalData};
+use super::server::{ProcessState, process_state_of};
+
+pub struct ChannelUsage {
+    pub disk_size: u64,
+    pub log_size: Option<u64>,
+    pub log_created: Option<i64>
+}
 
 pub struct ChannelManager<'l> {
     channel_path: PathBuf,
+    server_path: PathBuf,
     local_data_manager: &'l LocalDataManager,
-    channel: String
+    credentials: CredentialsManager,
+    channel: String,
+    // context.toml中记录的channel。channel被覆盖时与channel不同
+    using_channel: String
 }
 
 impl <'l> ChannelManager<'l> {
-    pub fn new(config: &LocalConfig, local_data_manager: &'l LocalDataManager) -> ChannelManager<'l> {
-        let channel = local_data_manager.read().using_channel.unwrap_or("default".to_string());
-        ChannelManager {
-            channel_path: config.work_path.appdata_path.join("channel"),
+    // 指定channel时只在本次调用中覆盖使用的channel，不写入context.toml
+    pub fn new(config: &LocalConfig, local_data_manager: &'l LocalDataManager, channel: Option<String>) -> Result<ChannelManager<'l>, HedgeError> {
+        let channel_path = config.work_path.appdata_path.join("channel");
+        let using_channel = local_data_manager.read()?.using_channel.unwrap_or("default".to_string());
+        let channel = match channel {
+            Some(c) => {
+                validate_name(&c)?;
+                if !channel_path.join(&c).is_dir() {
+                    return Result::Err(HedgeError::new(&format!("Channel {} does not exist.", c)).hint("Run 'hedge channel info' to list channels, or 'hedge channel create' to create it."))
+                }
+                c
+            },
+            None => using_channel.clone()
+        };
+        Result::Ok(ChannelManager {
+            channel_path,
+            server_path: config.work_path.server_path.clone(),
             local_data_manager,
-            channel
-        }
+            credentials: CredentialsManager::new(config),
+            channel,
+            using_channel
+        })
     }
-    pub fn use_channel(&self, channel: &str) {
-        self.local_data_manager.write(&LocalData { using_channel: Option::Some(channel.to_string()) });
+    pub fn use_channel(&self, channel: &str) -> Result<(), HedgeError> {
+        validate_name(channel)?;
+        if !self.channel_path.join(channel).is_dir() {
+            return Result::Err(HedgeError::new(&format!("Channel {} does not exist.", channel)).hint("Run 'hedge channel info' to list channels, or 'hedge channel create' to create it."))
+        }
+        self.local_data_manager.write(&LocalData { using_channel: Option::Some(channel.to_string()) })
     }
     pub fn current_channel(&self) -> &str {
         self.channel.as_str()
     }
-    pub fn list_channel(&self) -> Vec<String> {
+    pub fn list_channel(&self) -> Result<Vec<String>, HedgeError> {
         match fs::read_dir(&self.channel_path) {
-            Err(e) => panic!("Cannot read channel dir {}: {}", self.channel_path.to_str().unwrap(), e),
+            Err(e) => {
+                let hint = if e.kind() == ErrorKind::NotFound { "Channel data is created by Hedge app or server. Check work_path.appdata_path in config, or start Hedge once to initialize it." }else{ "Check the permission of this directory." };
+                Result::Err(HedgeError::from(e).context(&format!("Cannot read channel dir {}", self.channel_path.to_string_lossy())).hint(hint))
+            },
             Ok(d) => {
-                d.filter_map(|e| {
+                Result::Ok(d.filter_map(|e| {
                     e.ok()
-                        .filter(|f| f.file_type().is_ok_and(|f| f.is_dir()) && !f.file_name().to_str().unwrap().starts_with("."))
-                        .map(|f| f.file_name().to_str().unwrap().to_string())
-                }).collect()
+                        .filter(|f| f.file_type().is_ok_and(|f| f.is_dir()) && !f.file_name().to_string_lossy().starts_with("."))
+                        .map(|f| f.file_name().to_string_lossy().to_string())
+                }).collect())
             }
         }
     }
-}
\ No newline at end of file
+    pub fn create_channel(&self, channel: &str) -> Result<(), HedgeError> {
+        validate_name(channel)?;
+        let path = self.channel_path.join(channel);
+        if path.exists() {
+            return Result::Err(HedgeError::new(&format!("Channel {} already exists.", channel)))
+        }
+        fs::create_dir_all(&path).context(&format!("Cannot create channel dir {}", path.to_string_lossy()))?;
+        // 新channel默认为本地模式。Hedge app打开此channel时会补全其他选项
+        let client = serde_json::json!({ "loginOption": { "mode": "local" } });
+        fs::write(path.join("client.dat"), client.to_string()).context(&format!("Cannot write client file of channel {}", channel))?;
+        Result::Ok(())
+    }
+    pub fn remove_channel(&self, channel: &str) -> Result<(), HedgeError> {
+        let path = self.existing_channel(channel)?;
+        if channel == self.using_channel {
+            return Result::Err(HedgeError::new(&format!("Channel {} is in use.", channel)).hint("Switch to another channel by 'hedge channel use' before removing it."))
+        }
+        self.check_not_running(channel)?;
+        fs::remove_dir_all(&path).context(&format!("Cannot remove channel dir {}", path.to_string_lossy()))?;
+        // 残留的token会被之后同名的新channel继承
+        self.credentials.remove(channel).context("Cannot remove token of channel")?;
+        Result::Ok(())
+    }
+    pub fn rename_channel(&self, channel: &str, new_name: &str) -> Result<(), HedgeError> {
+        let path = self.existing_channel(channel)?;
+        let new_path = self.new_channel(new_name)?;
+        self.check_not_running(channel)?;
+        fs::rename(&path, &new_path).context(&format!("Cannot rename channel dir {}", path.to_string_lossy()))?;
+        self.credentials.rename(channel, new_name).context("Cannot move token of channel")?;
+        if channel == self.using_channel {
+            self.local_data_manager.write(&LocalData { using_channel: Option::Some(new_name.to_string()) })?;
+        }
+        Result::Ok(())
+    }
+    pub fn copy_channel(&self, channel: &str, new_name: &str) -> Result<(), HedgeError> {
+        let path = self.existing_channel(channel)?;
+        let new_path = self.new_channel(new_name)?;
+        // 运行中的server会持续写入数据库，此时复制的数据可能不完整
+        self.check_not_running(channel)?;
+        copy_dir(&path, &new_path).context(&format!("Cannot copy channel {} to {}", channel, new_name))?;
+        self.credentials.copy(channel, new_name).context("Cannot copy token of channel")
+    }
+    // channel目录的占用情况。server.log每次启动server时重新创建，其创建时间即上次启动的时间
+    pub fn channel_usage(&self, channel: &str) -> Result<ChannelUsage, HedgeError> {
+        let path = self.existing_channel(channel)?;
+        let log = fs::metadata(path.join("server.log")).ok();
+        Result::Ok(ChannelUsage {
+            disk_size: dir_size(&path).context(&format!("Cannot read channel dir {}", path.to_string_lossy()))?,
+            log_size: log.as_ref().map(|m| m.len()),
+            log_created: log.and_then(|m| m.created().ok()).map(|t| chrono::DateTime::<chrono::Utc>::from(t).timestamp_millis())
+        })
+    }
+    // 读取channel的PID文件，返回仍在运行的server进程的PID。进程已不存在或PID已被其他程序复用时视为未运行
+    pub fn running_pid(&self, channel: &str) -> Option<i64> {
+        let path = self.channel_path.join(channel);
+        let text = fs::read_to_string(path.join("server").join("PID")).ok()?;
+        let pid = serde_json::from_str::<serde_json::Value>(&text).ok()?.get("pid")?.as_i64()?;
+        match process_state_of(pid, &self.server_path, &path) {
+            ProcessState::Alive(pid) => Option::Some(pid),
+            _ => Option::None
+        }
+    }
+    fn existing_channel(&self, channel: &str) -> Result<PathBuf, HedgeError> {
+        validate_name(channel)?;
+        let path = self.channel_path.join(channel);
+        if !path.is_dir() {
+            return Result::Err(HedgeError::new(&format!("Channel {} does not exist.", channel)).hint("Run 'hedge channel info' to list channels."))
+        }
+        Result::Ok(path)
+    }
+    fn new_channel(&self, channel: &str) -> Result<PathBuf, HedgeError> {
+        validate_name(channel)?;
+        let path = self.channel_path.join(channel);
+        if path.exists() {
+            return Result::Err(HedgeError::new(&format!("Channel {} already exists.", channel)))
+        }
+        Result::Ok(path)
+    }
+    fn check_not_running(&self, channel: &str) -> Result<(), HedgeError> {
+        match self.running_pid(channel) {
+            Some(pid) => Result::Err(HedgeError::new(&format!("Server of channel {} is running (PID {}).", channel, pid)).hint("Stop it by 'hedge server stop' first.")),
+            None => Result::Ok(())
+        }
+    }
+}
+
+// channel名称同时是目录名，只允许字母、数字、'-'、'_'与'.'，且不能以'.'开头
+pub fn validate_name(channel: &str) -> Result<(), HedgeError> {
+    if channel.is_empty() || channel.len() > 64 {
+        return Result::Err(HedgeError::format(&format!("Invalid channel name '{}'", channel)).hint("Channel name should have 1 to 64 characters."))
+    }
+    if channel.starts_with('.') || !channel.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Result::Err(HedgeError::format(&format!("Invalid channel name '{}'", channel)).hint("Channel name can only contain letters, digits, '-', '_' and '.', and cannot start with '.'."))
+    }
+    Result::Ok(())
+}
+
+fn dir_size(path: &Path) -> Result<u64, std::io::Error> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        }else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Result::Ok(size)
+}
+
+// 递归复制目录。server的PID文件属于源channel的运行状态，不复制
+fn copy_dir(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        }else if !(entry.file_name() == "PID" && from.file_name().is_some_and(|f| f == "server")) {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Result::Ok(())
+}
@@ -1,35 +1,53 @@
 use std::{fs, env, path, io::ErrorKind, collections::HashMap};
 use serde::Deserialize;
 use home::home_dir;
+use crate::utils::error::HedgeError;
 
-pub fn load_config() -> LocalConfig {
+pub fn load_config() -> Result<LocalConfig, HedgeError> {
     let local_config_path = match env::var_os("LOCAL_CONFIG_PATH") {
-        Some(p) => p.to_str().unwrap().to_string(),
-        None => userdata_path().join("cli/config.toml").to_str().unwrap().to_string()
+        Some(p) => path::PathBuf::from(p),
+        None => userdata_path()?.join("cli/config.toml")
     };
     let local_config_text = match fs::read_to_string(&local_config_path) {
         Ok(data) => Option::Some(data),
         Err(e) => if e.kind() == ErrorKind::NotFound {
             Option::None
         }else{
-            panic!("Cannot load local config {}: {}", &local_config_path, e)
+            return Result::Err(HedgeError::from(e)
+                .context(&format!("Cannot load local config {}", local_config_path.to_string_lossy()))
+                .hint("Check the permission of this file, or set LOCAL_CONFIG_PATH to another config file."))
         }
     };
     let data: LocalConfigFile = match local_config_text {
-        Some(text) => match toml::from_str(&*text) {
+        Some(text) => match toml::from_str(&text) {
            Ok(data) => data,
-           Err(e) => panic!("Local config format error: {}", e)
+           Err(e) => {
+                // toml的错误信息包含多行的源码片段，这里只保留行号与错误本身
+                let file_name = local_config_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+                let message = match e.span() {
+                    Some(span) => format!("{} line {}: {}", file_name, text[..span.start].matches('\n').count() + 1, e.message()),
+                    None => format!("{}: {}", file_name, e.message())
+                };
+                return Result::Err(HedgeError::format(&message)
+                    .context("Local config format error")
+                    .hint(&format!("Fix {} at the location above. See config.template.toml for all available options.", local_config_path.to_string_lossy())))
+           }
         }
         None => default_config_file()
     };
 
-    LocalConfig {
+    let default_userdata_path = match &data.work_path.userdata_path {
+        Some(p) => path::PathBuf::from(p),
+        None => userdata_path()?
+    };
+
+    Result::Ok(LocalConfig {
         debug_mode: data.debug_mode.unwrap_or(false),
         work_path: WorkPath { 
             application_path: data.work_path.application_path.map(path::PathBuf::from),
-            userdata_path: data.work_path.userdata_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| userdata_path()),
-            server_path: data.work_path.server_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| data.work_path.userdata_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| userdata_path()).join("server")),
-            appdata_path: data.work_path.appdata_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| data.work_path.userdata_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| userdata_path()).join("appdata")),
+            server_path: data.work_path.server_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| default_userdata_path.join("server")),
+            appdata_path: data.work_path.appdata_path.as_ref().map(path::PathBuf::from).unwrap_or_else(|| default_userdata_path.join("appdata")),
+            userdata_path: default_userdata_path,
         },
         download: data.download.as_ref().map(|download| Download { 
             waiting_interval: download.waiting_interval, 
@@ -42,30 +60,55 @@ pub fn load_config() -> LocalConfig {
             proxy: Option::None,
             available_sites: Vec::new()
         }),
+        server: data.server.as_ref().map(|server| Server {
+            timeout_interval: server.timeout_interval.unwrap_or(60),
+            max_retries: server.max_retries.unwrap_or(2),
+            backoff_interval: server.backoff_interval.unwrap_or(500),
+            retry_on: server.retry_on.clone().unwrap_or_else(default_retry_on),
+            ca_cert: server.ca_cert.as_ref().map(path::PathBuf::from),
+            client_cert: server.client_cert.as_ref().map(path::PathBuf::from),
+            client_key: server.client_key.as_ref().map(path::PathBuf::from),
+            insecure_skip_verify: server.insecure_skip_verify.unwrap_or(false),
+            log_retention: server.log_retention.unwrap_or(5)
+        }).unwrap_or_else(|| Server {
+            timeout_interval: 60,
+            max_retries: 2,
+            backoff_interval: 500,
+            retry_on: default_retry_on(),
+            ca_cert: Option::None,
+            client_cert: Option::None,
+            client_key: Option::None,
+            insecure_skip_verify: false,
+            log_retention: 5
+        }),
         connect: data.connect.map(|c| Connect { 
             driver: c.driver, 
             url: c.url, 
             query: c.query, 
             parser: c.parser
         })
-    }
+    })
 }
 
-fn userdata_path() -> std::path::PathBuf {
+fn userdata_path() -> Result<std::path::PathBuf, HedgeError> {
     match home_dir() {
         Some(u) => {
             if cfg!(target_os = "macos") {
-                u.join("Library/Application Support/Hedge-v3")
+                Result::Ok(u.join("Library/Application Support/Hedge-v3"))
             }else if cfg!(target_os = "linux") {
-                u.join(".config/Hedge-v3")
+                Result::Ok(u.join(".config/Hedge-v3"))
             }else{
-                panic!("Unsupported system platform.")
+                Result::Err(HedgeError::new("Unsupported system platform.").hint("Set work_path.userdata_path in config, and specify the config file by LOCAL_CONFIG_PATH."))
             }
         },
-        None => panic!("Cannot read HOME dir.")
+        None => Result::Err(HedgeError::new("Cannot read HOME dir.").hint("Check the HOME environment variable."))
     }
 }
 
+fn default_retry_on() -> Vec<u16> {
+    vec![502, 503, 504]
+}
+
 fn default_config_file() -> LocalConfigFile {
     LocalConfigFile { 
         debug_mode: Option::None,
@@ -76,6 +119,7 @@ fn default_config_file() -> LocalConfigFile {
             userdata_path: Option::None
         },
         download: Option::None,
+        server: Option::None,
         connect: Option::None
     }
 }
@@ -84,6 +128,7 @@ pub struct LocalConfig {
     pub debug_mode: bool,
     pub work_path: WorkPath,
     pub download: Download,
+    pub server: Server,
     pub connect: Option<Connect>
 }
 
@@ -101,6 +146,18 @@ pub struct Download {
     pub available_sites: Vec<AvailableSite>
 }
 
+pub struct Server {
+    pub timeout_interval: u64,
+    pub max_retries: u32,
+    pub backoff_interval: u64,
+    pub retry_on: Vec<u16>,
+    pub ca_cert: Option<path::PathBuf>,
+    pub client_cert: Option<path::PathBuf>,
+    pub client_key: Option<path::PathBuf>,
+    pub insecure_skip_verify: bool,
+    pub log_retention: usize
+}
+
 pub struct Connect {
     pub driver: String,
     pub url: String,
@@ -119,6 +176,7 @@ struct LocalConfigFile {
     debug_mode: Option<bool>,
     work_path: LocalConfigFileWorkPath,
     download: Option<LocalConfigFileDownload>,
+    server: Option<LocalConfigFileServer>,
     connect: Option<LocalConfigDbConnect>
 }
 
@@ -138,6 +196,19 @@ struct LocalConfigFileDownload {
     available_sites: Option<Vec<AvailableSite>>
 }
 
+#[derive(Deserialize)]
+struct LocalConfigFileServer {
+    timeout_interval: Option<u64>,
+    max_retries: Option<u32>,
+    backoff_interval: Option<u64>,
+    retry_on: Option<Vec<u16>>,
+    ca_cert: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    insecure_skip_verify: Option<bool>,
+    log_retention: Option<usize>
+}
+
 #[derive(Deserialize)]
 struct LocalConfigDbConnect {
     driver: String,
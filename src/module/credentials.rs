@@ -0,0 +1,95 @@
+use std::{fs, io::{ErrorKind, Write}, path::PathBuf, collections::HashMap};
+use serde::{Deserialize, Serialize};
+use crate::utils::error::{HedgeError, ErrorContext};
+use super::config::LocalConfig;
+
+// 远程模式token的独立存储。与client.dat不同，此文件仅当前用户可读写
+#[derive(Deserialize, Serialize, Default)]
+struct Credentials {
+    #[serde(default)]
+    channels: HashMap<String, ChannelCredential>
+}
+
+#[derive(Deserialize, Serialize)]
+struct ChannelCredential {
+    token: String
+}
+
+#[derive(Clone)]
+pub struct CredentialsManager {
+    credentials_path: PathBuf
+}
+
+impl CredentialsManager {
+    pub fn new(config: &LocalConfig) -> CredentialsManager {
+        CredentialsManager {
+            credentials_path: config.work_path.userdata_path.join("cli/credentials.toml")
+        }
+    }
+    pub fn get(&self, channel: &str) -> Result<Option<String>, HedgeError> {
+        Result::Ok(self.read()?.channels.remove(channel).map(|c| c.token))
+    }
+    pub fn set(&self, channel: &str, token: &str) -> Result<(), HedgeError> {
+        let mut credentials = self.read()?;
+        credentials.channels.insert(channel.to_string(), ChannelCredential { token: token.to_string() });
+        self.write(&credentials)
+    }
+    // 返回是否确实删除了记录
+    pub fn remove(&self, channel: &str) -> Result<bool, HedgeError> {
+        let mut credentials = self.read()?;
+        if credentials.channels.remove(channel).is_none() {
+            return Result::Ok(false)
+        }
+        self.write(&credentials)?;
+        Result::Ok(true)
+    }
+    // channel改名时token随之移动；复制channel时一并复制token
+    pub fn rename(&self, channel: &str, new_name: &str) -> Result<(), HedgeError> {
+        let mut credentials = self.read()?;
+        if let Some(c) = credentials.channels.remove(channel) {
+            credentials.channels.insert(new_name.to_string(), c);
+            self.write(&credentials)?;
+        }
+        Result::Ok(())
+    }
+    pub fn copy(&self, channel: &str, new_name: &str) -> Result<(), HedgeError> {
+        match self.get(channel)? {
+            Some(token) => self.set(new_name, &token),
+            None => Result::Ok(())
+        }
+    }
+    fn read(&self) -> Result<Credentials, HedgeError> {
+        match fs::read_to_string(&self.credentials_path) {
+            Err(e) => if e.kind() == ErrorKind::NotFound {
+                Result::Ok(Credentials::default())
+            }else{
+                Result::Err(HedgeError::from(e).context(&format!("Cannot load credentials {}", self.credentials_path.to_string_lossy())))
+            },
+            Ok(t) => toml::from_str(&t).map_err(|e| HedgeError::format(e.message()))
+                .context(&format!("Credentials {} format error", self.credentials_path.to_string_lossy()))
+                .hint("Delete this file and run 'hedge channel login' again.")
+        }
+    }
+    fn write(&self, credentials: &Credentials) -> Result<(), HedgeError> {
+        let s = toml::to_string(credentials).map_err(|e| HedgeError::format(&e.to_string())).context("Credentials format error")?;
+        if let Some(parent) = self.credentials_path.parent() {
+            fs::create_dir_all(parent).context(&format!("Cannot create credentials dir {}", parent.to_string_lossy()))?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&self.credentials_path).context(&format!("Cannot create credentials {}", self.credentials_path.to_string_lossy()))?;
+        // mode只在创建时生效，已存在的文件也收紧权限
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.credentials_path, fs::Permissions::from_mode(0o600)).context(&format!("Cannot set permission of credentials {}", self.credentials_path.to_string_lossy()))?;
+        }
+        file.write_all(s.as_bytes()).context(&format!("Cannot write credentials {}", self.credentials_path.to_string_lossy()))?;
+        Result::Ok(())
+    }
+}
@@ -1,10 +1,10 @@
 mod sankakucomplex;
 
-use std::{time::Duration, error::Error, collections::HashMap};
+use std::{time::{Duration, Instant}, error::Error, collections::HashMap, sync::Arc};
 use reqwest::{Method, IntoUrl, RequestBuilder, Proxy, Response};
 use serde::Serialize;
-use crate::utils::error::ApplicationError;
-use super::{config::LocalConfig, api::source_data::{SourceDataUpdateForm, SourceTagForm, SourceBookForm, AdditionalInfoForm}};
+use crate::utils::error::{ApplicationError, HedgeError, ErrorContext};
+use super::{config::LocalConfig, request::{Exchange, Middleware}, api::source_data::{SourceDataUpdateForm, SourceTagForm, SourceBookForm, AdditionalInfoForm}};
 use sankakucomplex::download_for_sankakucomplex;
 
 
@@ -14,25 +14,30 @@ pub struct DownloadModule {
 }
 
 impl DownloadModule {
-    pub fn new(config: &LocalConfig) -> DownloadModule {
+    pub fn new(config: &LocalConfig) -> Result<DownloadModule, HedgeError> {
         let mut client_builder = reqwest::Client::builder();
         if let Some(proxy) = &config.download.proxy {
-            client_builder = client_builder.proxy(Proxy::all(proxy).unwrap());
+            let proxy = Proxy::all(proxy).map_err(|e| HedgeError::format(&e.to_string())).context(&format!("Invalid download proxy {}", proxy)).hint("Check download.proxy in config.")?;
+            client_builder = client_builder.proxy(proxy);
         }
         let adapter = Adapter {
-            client: client_builder.build().unwrap(),
+            client: client_builder.build().map_err(|e| HedgeError::new(&e.to_string())).context("Cannot build download client")?,
             timeout: config.download.timeout_interval.unwrap_or(20),
-            waiting: config.download.waiting_interval.unwrap_or(8)
+            waiting: config.download.waiting_interval.unwrap_or(8),
+            middlewares: Vec::new()
         };
         let mut available_sites: HashMap<String, String> = HashMap::new();
         for ele in &config.download.available_sites {
             available_sites.insert(ele.site.clone(), ele.rule.clone());
         }
         
-        DownloadModule { 
+        Result::Ok(DownloadModule { 
             adapter,
             available_sites
-        }
+        })
+    }
+    pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        self.adapter.middlewares.push(middleware);
     }
     pub async fn download(&self, site: &str, source_id: i64, _additional_info: Option<&HashMap<String, String>>) -> Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> {
         if let Some(rule) = self.available_sites.get(site) {
@@ -55,7 +60,8 @@ impl DownloadModule {
 pub struct Adapter {
     client: reqwest::Client,
     waiting: u64,
-    timeout: u64
+    timeout: u64,
+    middlewares: Vec<Arc<dyn Middleware>>
 }
 
 impl Adapter {
@@ -71,7 +77,7 @@ impl Adapter {
         let mut retry_cnt = 0;
         let mut error: Option<reqwest::Error> = Option::None;
         while retry_cnt < 3 {
-            let response = self.req(method.clone(), url).send().await;
+            let response = self.send(self.req(method.clone(), url), retry_cnt as u32).await;
             match response {
                 Ok(ok) => return Result::Ok((ok, retry_cnt)),
                 Err(e) => {
@@ -87,6 +93,21 @@ impl Adapter {
         }
         return Result::Err(Box::new(error.unwrap()));
     }
+    // 发送请求并通知中间件。响应body由调用者读取，因此不记录
+    async fn send(&self, builder: RequestBuilder, attempt: u32) -> Result<Response, reqwest::Error> {
+        let mut request = builder.build()?;
+        for middleware in &self.middlewares {
+            middleware.before(&mut request);
+        }
+        let (method, url, headers) = (request.method().clone(), request.url().clone(), request.headers().clone());
+        let start = Instant::now();
+        let response = self.client.execute(request).await;
+        let exchange = Exchange { method: &method, url: &url, headers: &headers, request_body: Option::None, attempt, elapsed: start.elapsed(), status: response.as_ref().ok().map(|r| r.status()), body: Option::None, error: response.as_ref().err() };
+        for middleware in &self.middlewares {
+            middleware.after(&exchange);
+        }
+        response
+    }
 }
 
 pub struct DownloadAttachInfo {
@@ -1,5 +1,6 @@
 use std::error::Error;
 use reqwest::Method;
+use crate::utils::error::HedgeError;
 use super::{Adapter, DownloadResult, DownloadAttachInfo, DownloadTag, DownloadBook};
 
 pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(DownloadResult, DownloadAttachInfo), Box<dyn Error>> {
@@ -10,25 +11,30 @@ pub async fn download_for_sankakucomplex(adapter: &Adapter, id: i64) -> Result<(
     let mut retry_sum_cnt = retry_cnt;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
-    let arr = value.as_array().unwrap();
+    let arr = as_array(&value)?;
     let obj = if let Some(obj) = arr.get(0) { obj } else {
         return Result::Err(Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Post {id} not found."))));
     };
 
     let tags: Option<Vec<DownloadTag>> = if let Some(tags) = obj.get("tags").filter(|t| t.is_array()).map(|t| t.as_array().unwrap()) {
-        Option::Some(tags.iter().map(|tag| {
+        Option::Some(tags.iter().map(|tag| -> Result<DownloadTag, HedgeError> {
             let tag_name = tag.get("tagName").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().replace('_', " "));
             let name_en = tag.get("name_en").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().replace('_', " "));
             let name_ja = tag.get("name_ja").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().to_string());
             let name = tag.get("name").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().replace('_', " "));
             let type_code = tag.get("type").filter(|f| f.is_i64()).map(|f| f.as_i64().unwrap());
-            DownloadTag {
-                tag_type: type_code.map(|c| get_tag_types(c).to_string()),
-                code: tag_name.as_ref().map(|f| f.clone()).unwrap_or_else(|| name_en.as_ref().map(|f| f.clone()).unwrap_or_else(|| name.as_ref().map(|f| f.clone()).expect("Tag name is not exist."))),
-                name: tag_name.as_ref().map(|f| f.clone()).or_else(|| name_en.as_ref().map(|f| f.clone()).or_else(|| name.as_ref().map(|f| f.clone()))),
+            let tag_type = match type_code {
+                Some(c) => Option::Some(get_tag_types(c)?.to_string()),
+                None => Option::None
+            };
+            let code = tag_name.clone().or_else(|| name_en.clone()).or_else(|| name.clone()).ok_or_else(|| HedgeError::format("Tag name is not exist."))?;
+            Result::Ok(DownloadTag {
+                tag_type,
+                code,
+                name: tag_name.or(name_en).or(name),
                 other_name: name_ja,
-            }
-        }).collect())
+            })
+        }).collect::<Result<Vec<_>, _>>()?)
     }else{
         Option::None
     };
@@ -83,8 +89,8 @@ async fn download_for_children(adapter: &Adapter, id: i64) -> Result<(Vec<i64>,
     let text = res.text().await?;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
-    let arr = value.as_array().unwrap();
-    let ret = arr.iter().map(|child| child.get("id").filter(|f| f.is_i64()).map(|f| f.as_i64().unwrap()).expect("Children id is not exist.")).collect();
+    let arr = as_array(&value)?;
+    let ret = arr.iter().map(|child| child.get("id").and_then(|f| f.as_i64()).ok_or_else(|| HedgeError::format("Children id is not exist."))).collect::<Result<_, _>>()?;
 
     Result::Ok((ret, retry_cnt))
 }
@@ -95,31 +101,35 @@ async fn download_for_book(adapter: &Adapter, id: i64) -> Result<(Vec<DownloadBo
     let text = res.text().await?;
 
     let value: serde_json::Value = serde_json::from_str(text.as_str())?;
-    let arr = value.as_array().unwrap();
+    let arr = as_array(&value)?;
     let ret = arr.iter().map(|book| {
-        let id = book.get("id").filter(|f| f.is_i64()).map(|f| f.as_i64().unwrap()).expect("Book id is not exist.");
+        let id = book.get("id").and_then(|f| f.as_i64()).ok_or_else(|| HedgeError::format("Book id is not exist."))?;
         let name = book.get("name").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().to_string());
         let name_ja = book.get("name_ja").filter(|f| f.is_string()).map(|f| f.as_str().unwrap().to_string());
-        DownloadBook { 
+        Result::Ok(DownloadBook { 
             code: id.to_string(),
             title: name,
             other_title: name_ja
-        }
-    }).collect();
+        })
+    }).collect::<Result<_, HedgeError>>()?;
 
     Result::Ok((ret, retry_cnt))
 }
 
-fn get_tag_types(type_code: i64) -> &'static str {
+fn as_array(value: &serde_json::Value) -> Result<&Vec<serde_json::Value>, HedgeError> {
+    value.as_array().ok_or_else(|| HedgeError::format("Response is not an array."))
+}
+
+fn get_tag_types(type_code: i64) -> Result<&'static str, HedgeError> {
     match type_code {
-        1 => "artist",     // 画师
-        2 => "studio",     // 团体信息
-        3 => "copyright",  // 所属作品
-        4 => "character",  // 角色
-        8 => "medium",     // 图像信息
-        9 => "meta",       // 元信息
-        0 => "general",    // 内容
-        5 => "genre",      // 特别内容
-        _ => panic!("Unknown tag type {}.", type_code)
+        1 => Result::Ok("artist"),     // 画师
+        2 => Result::Ok("studio"),     // 团体信息
+        3 => Result::Ok("copyright"),  // 所属作品
+        4 => Result::Ok("character"),  // 角色
+        8 => Result::Ok("medium"),     // 图像信息
+        9 => Result::Ok("meta"),       // 元信息
+        0 => Result::Ok("general"),    // 内容
+        5 => Result::Ok("genre"),      // 特别内容
+        _ => Result::Err(HedgeError::format(&format!("Unknown tag type {}.", type_code)))
     }
 }
\ No newline at end of file
@@ -17,7 +17,7 @@ impl <'t> ImportModule <'t> {
         ImportModule { server_manager }
     }
     pub async fn list(&mut self) -> Result<ListResult<ImportImageRes>, Box<dyn Error>> {
-        self.server_manager.req(Method::GET, "/api/imports").await
+        self.server_manager.request(Method::GET, "/api/imports").fetch().await
     }
     pub async fn add(&mut self, filepath: &PathBuf, remove: bool) -> Result<IdRes, Box<dyn Error>> {
         if self.server_manager.access.remote_mode {
@@ -35,13 +35,13 @@ impl <'t> ImportModule <'t> {
                 .part("file", file_part)
                 .text("creationTime", created.map(|f| f.to_rfc3339()).unwrap_or_else(|| modified.to_rfc3339()))
                 .text("modificationTime", modified.to_rfc3339());
-            self.server_manager.req_with_form(Method::POST, "/api/imports/upload", form).await
+            self.server_manager.request(Method::POST, "/api/imports/upload").form(form).fetch().await
         }else{
             let body = json!({
                 "filepath": filepath.to_str().unwrap(),
                 "mobileImport": remove
             });
-            self.server_manager.req_with_body(Method::POST, "/api/imports/import", body).await
+            self.server_manager.request(Method::POST, "/api/imports/import").body(body).fetch().await
         }
     }
     pub async fn batch(&mut self, partition_time: Option<NaiveDate>, create_time: Option<OrderTimeType>, order_time: Option<OrderTimeType>, analyse_source: bool) -> Result<(), Box<dyn Error>> {
@@ -51,13 +51,13 @@ impl <'t> ImportModule <'t> {
             "analyseSource": analyse_source,
             "partitionTime": partition_time.map(|p| p.format("%Y-%m-%d").to_string())
         });
-        self.server_manager.req_without_res(Method::POST, "/api/imports/batch-update", body).await
+        self.server_manager.request(Method::POST, "/api/imports/batch-update").body(body).execute().await
     }
     pub async fn save(&mut self) -> Result<ImportSaveRes, Box<dyn Error>> {
         let body = json!({
             "target": Option::<Vec<i32>>::None
         });
-        self.server_manager.req_with_body(Method::POST, "/api/imports/save", body).await
+        self.server_manager.request(Method::POST, "/api/imports/save").body(body).fetch().await
     }
 }
 
@@ -0,0 +1,17 @@
+use tokio::sync::watch;
+
+// 与server的连接丢失的通知。耗时的命令在安全点检查它，连接丢失时停止后续工作，并照常执行收尾(例如保存断点)
+#[derive(Clone)]
+pub struct LifetimeWatch {
+    receiver: watch::Receiver<Option<String>>
+}
+
+impl LifetimeWatch {
+    pub fn new(receiver: watch::Receiver<Option<String>>) -> LifetimeWatch {
+        LifetimeWatch { receiver }
+    }
+    // 连接已丢失时返回原因。没有在维护server运行时永远返回None
+    pub fn lost(&self) -> Option<String> {
+        self.receiver.borrow().clone()
+    }
+}
@@ -3,6 +3,7 @@ use std::{fs, io::ErrorKind};
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use toml;
+use crate::utils::error::{HedgeError, ErrorContext};
 use super::config::LocalConfig;
 
 #[derive(Deserialize, Serialize)]
@@ -20,52 +21,40 @@ impl LocalDataManager {
             local_data_path: config.work_path.userdata_path.join("cli/context.toml")
         }
     }
-    pub fn read(&self) -> LocalData {
+    pub fn read(&self) -> Result<LocalData, HedgeError> {
         match fs::read_to_string(&self.local_data_path) {
             Err(e) => if e.kind() == ErrorKind::NotFound {
-                LocalData {
+                Result::Ok(LocalData {
                     using_channel: Option::None
-                }
+                })
             }else{
-                panic!("Cannot load local data {}: {}", self.local_data_path.to_str().unwrap(), e)
+                Result::Err(HedgeError::from(e).context(&format!("Cannot load local data {}", self.local_data_path.to_string_lossy())))
             },
             Ok(t) => match toml::from_str(&t) {
-                Err(e) => panic!("Local data format error: {}", e),
-                Ok(t) => t
+                Err(e) => Result::Err(HedgeError::format(e.message())
+                    .context(&format!("Local data {} format error", self.local_data_path.to_string_lossy()))
+                    .hint("This file only records the using channel. Delete it to reset.")),
+                Ok(t) => Result::Ok(t)
             }
         }
     }
-    pub fn write(&self, data: &LocalData) {
-        match toml::to_string(data) {
-            Err(e) => panic!("Local data format error: {}", e),
-            Ok(s) => {
-                
-                match fs::write(&self.local_data_path, &s) {
-                    Err(e) => {
-                        if e.kind() == ErrorKind::NotFound {
-                            self.write_and_create(&s);
-                        }else{
-                            panic!("Cannot write local data {}: {}", self.local_data_path.to_str().unwrap(), e)
-                        }
-                    },
-                    Ok(_) => {}
-                }
-            }
+    pub fn write(&self, data: &LocalData) -> Result<(), HedgeError> {
+        let s = toml::to_string(data).map_err(|e| HedgeError::format(&e.to_string())).context("Local data format error")?;
+        match fs::write(&self.local_data_path, &s) {
+            Err(e) => if e.kind() == ErrorKind::NotFound {
+                self.write_and_create(&s)
+            }else{
+                Result::Err(HedgeError::from(e).context(&format!("Cannot write local data {}", self.local_data_path.to_string_lossy())))
+            },
+            Ok(_) => Result::Ok(())
         }
     }
-    fn write_and_create(&self, data: &String) {
+    fn write_and_create(&self, data: &str) -> Result<(), HedgeError> {
         if let Some(parent) = self.local_data_path.parent() {
-            match fs::create_dir_all(parent) {
-                Err(e) => panic!("Cannot create local data dir {}: {}", parent.to_str().unwrap(), e),    
-                Ok(_) => {}
-            }
-        }
-        match fs::File::create(&self.local_data_path) {
-            Err(e) => panic!("Cannot create local data {}: {}", self.local_data_path.to_str().unwrap(), e),
-            Ok(mut file) => match file.write(data.as_bytes()) {
-                Err(e) => panic!("Cannot write local data {}: {}", self.local_data_path.to_str().unwrap(), e),
-                Ok(_) => {}
-            }
+            fs::create_dir_all(parent).context(&format!("Cannot create local data dir {}", parent.to_string_lossy()))?;
         }
+        let mut file = fs::File::create(&self.local_data_path).context(&format!("Cannot create local data {}", self.local_data_path.to_string_lossy()))?;
+        file.write_all(data.as_bytes()).context(&format!("Cannot write local data {}", self.local_data_path.to_string_lossy()))?;
+        Result::Ok(())
     }
 }
\ No newline at end of file
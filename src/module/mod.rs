@@ -1,7 +1,13 @@
 pub mod config;
 pub mod local_data;
 pub mod channel;
+pub mod credentials;
 pub mod server;
+pub mod server_log;
+pub mod lifetime;
+pub mod service;
+pub mod request;
+pub mod trace;
 pub mod import;
 pub mod api;
 pub mod download;
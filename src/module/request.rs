@@ -0,0 +1,266 @@
+use std::{fs, error::Error, sync::Arc, time::{Duration, Instant}};
+use reqwest::{Certificate, Identity, Method, Request, StatusCode, Url, multipart::Form, header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE}};
+use serde::de::DeserializeOwned;
+use crate::utils::error::{ApiResultError, HedgeError, HttpStatusError, ErrorContext};
+use super::{config::Server, server::ErrorResult};
+
+// 请求管线。所有对server的请求都经过它：拼接地址、执行中间件、按策略重试、解析响应与错误
+#[derive(Clone)]
+pub struct Requester {
+    client: Arc<reqwest::Client>,
+    retry: RetryPolicy,
+    middlewares: Vec<Arc<dyn Middleware>>
+}
+
+// 中间件在每次尝试发送前后被调用。before可以修改请求，after可以观察结果(例如日志)
+pub trait Middleware: Send + Sync {
+    fn before(&self, _request: &mut Request) {}
+    fn after(&self, _exchange: &Exchange) {}
+}
+
+// 一次尝试的请求与结果。请求失败时status与body为None；request_body在body不是文本(例如multipart)时为None
+pub struct Exchange<'t> {
+    pub method: &'t Method,
+    pub url: &'t Url,
+    pub headers: &'t HeaderMap,
+    pub request_body: Option<&'t str>,
+    pub attempt: u32,
+    pub elapsed: Duration,
+    pub status: Option<StatusCode>,
+    pub body: Option<&'t str>,
+    pub error: Option<&'t reqwest::Error>
+}
+
+// 鉴权也作为中间件实现，附加Bearer token
+pub struct BearerAuth {
+    token: String
+}
+
+impl Middleware for BearerAuth {
+    fn before(&self, request: &mut Request) {
+        if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", self.token)) {
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+    }
+}
+
+// 超时与重试策略。timeout是普通请求的总时长限制(multipart不受限)。连接失败、超时，或响应状态码在retry_on中时，按指数退避重试
+#[derive(Clone)]
+struct RetryPolicy {
+    timeout: Duration,
+    max_retries: u32,
+    backoff: Duration,
+    retry_on: Vec<u16>
+}
+
+impl Requester {
+    pub fn new(config: &Server) -> Result<Requester, HedgeError> {
+        Result::Ok(Requester {
+            client: Arc::new(build_client(config)?),
+            retry: RetryPolicy {
+                timeout: Duration::from_secs(config.timeout_interval),
+                max_retries: config.max_retries,
+                backoff: Duration::from_millis(config.backoff_interval),
+                retry_on: config.retry_on.clone()
+            },
+            middlewares: Vec::new()
+        })
+    }
+    pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        self.middlewares.push(middleware);
+    }
+    pub fn request(&self, address: Option<&str>, token: Option<&str>, method: Method, path: &str) -> ServerRequest<'_> {
+        let url = address.map(|address| format!("{}{}", address, path)).unwrap_or_else(|| path.to_string());
+        ServerRequest {
+            requester: self,
+            url,
+            token: token.map(|t| t.to_string()),
+            query: Vec::new(),
+            body: RequestBody::Empty,
+            max_retries: if is_idempotent(&method) { self.retry.max_retries }else{ 0 },
+            method
+        }
+    }
+}
+
+// 超时或5xx时请求可能已经被server处理，只有幂等的请求默认重试，其他请求通过retry()按需开启
+fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE)
+}
+
+// 远程模式可能使用自签名证书或双向认证，按配置添加CA与客户端证书
+fn build_client(config: &Server) -> Result<reqwest::Client, HedgeError> {
+    let mut builder = reqwest::Client::builder().connect_timeout(Duration::from_secs(config.timeout_interval));
+    if let Some(path) = &config.ca_cert {
+        let pem = fs::read(path).context(&format!("Cannot read CA certificate {}", path.to_string_lossy())).hint("Check server.ca_cert in config.")?;
+        let cert = Certificate::from_pem(&pem).map_err(|e| HedgeError::format(&e.to_string())).context(&format!("Invalid CA certificate {}", path.to_string_lossy())).hint("server.ca_cert should be a PEM file.")?;
+        builder = builder.add_root_certificate(cert);
+    }
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = fs::read(cert_path).context(&format!("Cannot read client certificate {}", cert_path.to_string_lossy())).hint("Check server.client_cert in config.")?;
+            let key = fs::read(key_path).context(&format!("Cannot read client key {}", key_path.to_string_lossy())).hint("Check server.client_key in config.")?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key).map_err(|e| HedgeError::format(&e.to_string())).context("Invalid client certificate").hint("server.client_cert should be a PEM file, and server.client_key should be a PKCS#8 PEM private key.")?;
+            builder = builder.identity(identity);
+        },
+        (None, None) => {},
+        _ => return Result::Err(HedgeError::format("server.client_cert and server.client_key should be specified together.").context("Invalid client certificate"))
+    }
+    if config.insecure_skip_verify {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    builder.build().map_err(|e| HedgeError::new(&e.to_string())).context("Cannot build server client")
+}
+
+enum RequestBody {
+    Empty,
+    Json(serde_json::Value),
+    Form(Form)
+}
+
+pub struct ServerRequest<'t> {
+    requester: &'t Requester,
+    method: Method,
+    url: String,
+    token: Option<String>,
+    query: Vec<(String, String)>,
+    body: RequestBody,
+    max_retries: u32
+}
+
+impl <'t> ServerRequest<'t> {
+    pub fn query(mut self, query: &[(&str, String)]) -> Self {
+        self.query.extend(query.iter().map(|(k, v)| (k.to_string(), v.clone())));
+        self
+    }
+    pub fn body(mut self, body: serde_json::Value) -> Self {
+        self.body = RequestBody::Json(body);
+        self
+    }
+    pub fn form(mut self, form: Form) -> Self {
+        self.body = RequestBody::Form(form);
+        self
+    }
+    // 不重试。用于本身就会被轮询的请求
+    pub fn no_retry(mut self) -> Self {
+        self.max_retries = 0;
+        self
+    }
+    // 按策略重试。用于重复执行也不会产生额外影响的POST/PATCH请求，例如设置为固定的值
+    pub fn retry(mut self) -> Self {
+        self.max_retries = self.requester.retry.max_retries;
+        self
+    }
+    // 发送请求并将响应解析为T
+    pub async fn fetch<T: DeserializeOwned>(self) -> Result<T, Box<dyn Error>> {
+        let target = format!("{} {}", self.method, self.url);
+        let text = self.send().await?;
+        match serde_json::from_str(&text) {
+            Ok(t) => Result::Ok(t),
+            Err(e) => Result::Err(Box::new(HedgeError::format(&e.to_string()).context(&format!("Cannot decode response of {}", target))))
+        }
+    }
+    // 发送请求并忽略响应内容
+    pub async fn execute(self) -> Result<(), Box<dyn Error>> {
+        self.send().await?;
+        Result::Ok(())
+    }
+    async fn send(self) -> Result<String, Box<dyn Error>> {
+        let retry = &self.requester.retry;
+        let mut builder = self.requester.client.request(self.method, &self.url);
+        if !self.query.is_empty() {
+            builder = builder.query(&self.query);
+        }
+        builder = match self.body {
+            RequestBody::Empty => builder.timeout(retry.timeout),
+            RequestBody::Json(body) => builder.timeout(retry.timeout).header(CONTENT_TYPE, "application/json").body(serde_json::to_string(&body)?),
+            // multipart上传大文件耗时与文件大小相关，不限制总时长，只由client的connect_timeout限制建立连接
+            RequestBody::Form(form) => builder.multipart(form)
+        };
+        let mut request = builder.build()?;
+        if let Some(token) = self.token {
+            BearerAuth { token }.before(&mut request);
+        }
+        for middleware in &self.requester.middlewares {
+            middleware.before(&mut request);
+        }
+
+        let mut attempt = 0;
+        loop {
+            // 最后一次尝试，或body无法复制(例如multipart流)时，直接发送并返回其结果
+            let current = match request.try_clone() {
+                Some(r) if attempt < self.max_retries => r,
+                _ => return self.requester.attempt(request, attempt).await.0
+            };
+            match self.requester.attempt(current, attempt).await {
+                (Ok(text), _) => return Result::Ok(text),
+                (Err(e), false) => return Result::Err(e),
+                (Err(_), true) => {}
+            }
+            async_std::task::sleep(retry.backoff * 2u32.saturating_pow(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl Requester {
+    // 执行一次尝试，返回结果以及失败时是否可以重试
+    async fn attempt(&self, request: Request, attempt: u32) -> (Result<String, Box<dyn Error>>, bool) {
+        let (method, url, headers) = (request.method().clone(), request.url().clone(), request.headers().clone());
+        // 没有中间件时不必复制body
+        let request_body = if self.middlewares.is_empty() { Option::None }else{ request.body().and_then(|b| b.as_bytes()).map(|b| String::from_utf8_lossy(b).to_string()) };
+        let exchange = |elapsed, status, body, error| Exchange { method: &method, url: &url, headers: &headers, request_body: request_body.as_deref(), attempt, elapsed, status, body, error };
+        let start = Instant::now();
+        let response = self.client.execute(request).await;
+        let (status, text) = match response {
+            Err(e) => {
+                self.notify(&exchange(start.elapsed(), Option::None, Option::None, Option::Some(&e)));
+                let retryable = e.is_connect() || e.is_timeout();
+                return (Result::Err(Box::new(e)), retryable)
+            },
+            Ok(res) => {
+                let status = res.status();
+                match res.text().await {
+                    Ok(text) => (status, text),
+                    Err(e) => {
+                        self.notify(&exchange(start.elapsed(), Option::Some(status), Option::None, Option::Some(&e)));
+                        let retryable = e.is_timeout();
+                        return (Result::Err(Box::new(e)), retryable)
+                    }
+                }
+            }
+        };
+        self.notify(&exchange(start.elapsed(), Option::Some(status), Option::Some(&text), Option::None));
+
+        if status.is_success() || status.is_redirection() {
+            return (Result::Ok(text), false)
+        }
+        let retryable = self.retry.retry_on.contains(&status.as_u16());
+        // 错误响应优先解析为server的错误结构；不是JSON时(例如反向代理返回的页面)，以HTTP状态码报告
+        let error: Box<dyn Error> = match serde_json::from_str::<ErrorResult>(&text) {
+            Ok(err) => Box::new(ApiResultError::new(&err.code, &err.message)),
+            Err(_) => Box::new(HttpStatusError::new(status.as_u16(), status.canonical_reason().unwrap_or(""), &text))
+        };
+        (Result::Err(error), retryable)
+    }
+    fn notify(&self, exchange: &Exchange) {
+        for middleware in &self.middlewares {
+            middleware.after(exchange);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_idempotent_methods_retry_by_default() {
+        for method in [Method::GET, Method::HEAD, Method::PUT, Method::DELETE] {
+            assert!(is_idempotent(&method), "{} should be retried", method);
+        }
+        for method in [Method::POST, Method::PATCH] {
+            assert!(!is_idempotent(&method), "{} should not be retried", method);
+        }
+    }
+}
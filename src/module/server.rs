@@ -1,18 +1,26 @@
-use std::{path::PathBuf, fs, fmt, process::{Command, Stdio}, time::Duration, error::Error, sync::Arc, io::{BufReader, prelude::BufRead}};
-use sysinfo::{System, SystemExt, Pid, Signal, ProcessExt};
-use reqwest::{Method, IntoUrl};
-use serde::{Deserialize, Serialize};
+use std::{path::{Path, PathBuf}, fs, fmt, process::{Command, Stdio}, time::Duration, error::Error, sync::Arc};
+use sysinfo::{System, SystemExt, Pid, Signal, ProcessExt, ProcessStatus};
+use reqwest::Method;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json;
+use tokio::sync::watch;
 
-use crate::utils::error::{ApplicationError, ApiResultError};
+use crate::utils::error::{ApplicationError, HedgeError, ErrorContext};
 
-use super::{config::LocalConfig, channel::ChannelManager};
+use super::{config::LocalConfig, channel::ChannelManager, credentials::CredentialsManager, server_log::{LogOptions, print_log, rotate_log}, lifetime::LifetimeWatch, request::{Requester, ServerRequest, Middleware}};
+
+// list_all每页的数量
+const LIST_ALL_LIMIT: u32 = 500;
 
 pub struct ServerManager {
     server_path: PathBuf,
     appdata_path: PathBuf,
     channel: String,
-    client: Arc<reqwest::Client>,
+    log_retention: usize,
+    requester: Requester,
+    credentials: CredentialsManager,
+    // 与server的连接丢失时写入原因
+    lifetime: Arc<watch::Sender<Option<String>>>,
     pub access: Access
 }
 
@@ -23,335 +31,541 @@ pub struct Access {
     token: Option<String>
 }
 
+impl Access {
+    fn request<'t>(&self, requester: &'t Requester, method: Method, path: &str) -> ServerRequest<'t> {
+        requester.request(self.address.as_deref(), self.token.as_deref(), method, path)
+    }
+}
+
 impl ServerManager {
-    pub fn new(config: &LocalConfig, channel_manager: &ChannelManager) -> ServerManager {
-        ServerManager { 
+    pub fn new(config: &LocalConfig, channel_manager: &ChannelManager) -> Result<ServerManager, HedgeError> {
+        Result::Ok(ServerManager { 
             server_path: config.work_path.server_path.clone(),
             appdata_path: config.work_path.appdata_path.clone(),
             channel: channel_manager.current_channel().to_string(),
-            client: Arc::new(reqwest::Client::new()),
+            log_retention: config.server.log_retention,
+            requester: Requester::new(&config.server)?,
+            credentials: CredentialsManager::new(config),
+            lifetime: Arc::new(watch::channel(Option::None).0),
+            access: Access { address: Option::None, token: Option::None, remote_mode: false }
+        })
+    }
+    // 以相同的配置访问另一个channel的server
+    pub fn with_channel(&self, channel: &str) -> ServerManager {
+        ServerManager {
+            server_path: self.server_path.clone(),
+            appdata_path: self.appdata_path.clone(),
+            channel: channel.to_string(),
+            log_retention: self.log_retention,
+            requester: self.requester.clone(),
+            credentials: self.credentials.clone(),
+            lifetime: Arc::new(watch::channel(Option::None).0),
             access: Access { address: Option::None, token: Option::None, remote_mode: false }
         }
     }
-    pub async fn status(&mut self) -> ServerStatus {
-        let client_option = self.read_client_option();
+    pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        self.requester.add_middleware(middleware);
+    }
+    pub fn lifetime_watch(&self) -> LifetimeWatch {
+        LifetimeWatch::new(self.lifetime.subscribe())
+    }
+    pub async fn status(&mut self) -> Result<ServerStatus, HedgeError> {
+        let client_option = self.read_client_option()?;
         if client_option.is_none() {
-            return ServerStatus { status: ServerStatusType::NotInitialized, host: Option::None, pid: Option::None, port: Option::None, start_time: Option::None, remote_mode: false }
+            return Result::Ok(ServerStatus { status: ServerStatusType::NotInitialized, host: Option::None, pid: Option::None, port: Option::None, start_time: Option::None, remote_mode: false, detail: Option::None })
         }
         let stat = if client_option.as_ref().unwrap().login_option.mode == "remote" {
-            let host = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().host.clone();
-            let token = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().token.clone();
+            let (host, token) = self.remote_login(client_option.as_ref().unwrap())?;
             self.set_access_remote(host.clone(), token);
-            ServerStatus { status: ServerStatusType::NotInitialized, pid: Option::None, start_time: Option::None, port: Option::None, host: Option::Some(host), remote_mode: true }
+            ServerStatus { status: ServerStatusType::NotInitialized, pid: Option::None, start_time: Option::None, port: Option::None, host: Option::Some(host), remote_mode: true, detail: Option::None }
         }else{
-            let pid_file = self.read_pid_file();
+            let pid_file = self.read_pid_file()?;
             if pid_file.is_none() {
-                return ServerStatus { status: ServerStatusType::Stop, host: Option::None, pid: Option::None, port: Option::None, start_time: Option::None, remote_mode: false }
+                return Result::Ok(ServerStatus { status: ServerStatusType::Stop, host: Option::None, pid: Option::None, port: Option::None, start_time: Option::None, remote_mode: false, detail: Option::None })
             }
             let pid_file = pid_file.unwrap();
+            // server已异常退出时只报告状态。残留的PID文件由启动流程清理
+            match self.process_state()? {
+                ProcessState::Dead(pid) => {
+                    return Result::Ok(ServerStatus { status: ServerStatusType::Crashed, host: Option::None, pid: Option::Some(pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false, detail: Option::Some(format!("Server process {} exited unexpectedly.", pid)) })
+                },
+                ProcessState::Foreign(pid) => {
+                    return Result::Ok(ServerStatus { status: ServerStatusType::Stale, host: Option::None, pid: Option::Some(pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false, detail: Option::Some(format!("PID {} in PID file belongs to another program now.", pid)) })
+                },
+                _ => {}
+            }
             if pid_file.port.is_none() || pid_file.token.is_none() {
-                return ServerStatus { status: ServerStatusType::Starting, host: Option::None, pid: Option::Some(pid_file.pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false }
+                return Result::Ok(ServerStatus { status: ServerStatusType::Starting, host: Option::None, pid: Option::Some(pid_file.pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false, detail: Option::None })
             }
             self.set_access(pid_file.port.unwrap(), pid_file.token.unwrap());
-            ServerStatus { status: ServerStatusType::Starting, host: Option::None, pid: Option::Some(pid_file.pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false }
+            ServerStatus { status: ServerStatusType::Starting, host: Option::None, pid: Option::Some(pid_file.pid), port: pid_file.port, start_time: Option::Some(pid_file.start_time), remote_mode: false, detail: Option::None }
         };
-        match self.req(Method::GET, "/app/health").await {
+        Result::Ok(match self.health().await {
             Err(_) => {
                 if stat.remote_mode {
-                    ServerStatus { status: ServerStatusType::ConnectionError, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode }
+                    ServerStatus { status: ServerStatusType::ConnectionError, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode, detail: Option::None }
+                }else if let Some(conflict) = stat.port.zip(stat.pid).and_then(|(port, pid)| port_conflict(port, pid)) {
+                    ServerStatus { status: ServerStatusType::Stale, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode, detail: Option::Some(conflict) }
                 }else{
-                    ServerStatus { status: ServerStatusType::Starting, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode }
+                    ServerStatus { status: ServerStatusType::Starting, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode, detail: Option::None }
                 }
             },
             Ok(data) => {
                 let d: AppStatusRes = data;
                 if d.status != "READY" {
-                    ServerStatus { status: ServerStatusType::Loading, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode }
+                    ServerStatus { status: ServerStatusType::Loading, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode, detail: Option::None }
                 }else{
-                    ServerStatus { status: ServerStatusType::Running, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode }
+                    ServerStatus { status: ServerStatusType::Running, host: stat.host, pid: stat.pid, port: stat.port, start_time: stat.start_time, remote_mode: stat.remote_mode, detail: Option::None }
                 }
             }
-        }
+        })
     }
     pub async fn waiting_for_start(&mut self) -> Result<(), Box<dyn Error>> {
-        let client_option = self.read_client_option();
+        let client_option = self.read_client_option()?;
         if client_option.is_none() {
-            return Result::Err(Box::new(ApplicationError::new("Channel is not initialized.")))
+            return Result::Err(Box::new(HedgeError::new("Channel is not initialized.").hint("Start Hedge app once with this channel to initialize it.")))
         }
         if client_option.as_ref().unwrap().login_option.mode != "remote" {
-            let pid_file = self.read_pid_file();
-            if pid_file.is_none() {
-                self.start_server();
-            }
-            if self.check_connection().await {
-                self.single_signal(10000).await?;
-                Result::Ok(())
-            }else{
-                Result::Err(Box::new(ApplicationError::new("Check connection failed: timed out.")))
+            self.clear_stale_pid_file()?;
+            if self.read_pid_file()?.is_none() {
+                self.start_server()?;
             }
+            self.waiting_for_ready().await
         }else{
-            let host = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().host.clone();
-            let token = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().token.clone();
+            let (host, token) = self.remote_login(client_option.as_ref().unwrap())?;
             self.set_access_remote(host, token);
             Result::Ok(())
         }
     }
+    // 不启动server，只等待已由其他方式(例如systemd)启动的server就绪
+    pub async fn waiting_for_ready(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.check_connection().await? {
+            self.single_signal(10000).await?;
+            Result::Ok(())
+        }else{
+            Result::Err(Box::new(ApplicationError::new("Check connection failed: timed out.")))
+        }
+    }
+    // 在命令执行期间保持server运行。与server的连接丢失时通过lifetime_watch通知
     pub async fn maintaining_for_start(&mut self) -> Result<(), Box<dyn Error>> {
-        //FUTURE 保持运行的操作可以更换为ws连接实现，更稳定
-        let client_option = self.read_client_option();
+        let client_option = self.read_client_option()?;
         if client_option.is_none() {
-            return Result::Err(Box::new(ApplicationError::new("Channel is not initialized.")))
+            return Result::Err(Box::new(HedgeError::new("Channel is not initialized.").hint("Start Hedge app once with this channel to initialize it.")))
         }
         if client_option.as_ref().unwrap().login_option.mode != "remote" {
-            let pid_file = self.read_pid_file();
-            if pid_file.is_none() {
-                self.start_server();
+            self.clear_stale_pid_file()?;
+            if self.read_pid_file()?.is_none() {
+                self.start_server()?;
             }
-            if self.check_connection().await {
+            if self.check_connection().await? {
                 self.single_signal(30000).await?;
-    
-                let maintain = MaintainComponent { client: Arc::clone(&self.client), access: self.access.clone() };
-                
+
+                //FUTURE 保持运行的操作可以更换为ws连接实现，更稳定。server目前没有提供此类连接的约定，因此通过signal发送失败判断server丢失
+                let maintain = MaintainComponent { requester: self.requester.clone(), access: self.access.clone() };
+                let sender = self.lifetime.clone();
                 tokio::spawn(async move {
                     loop {
                         async_std::task::sleep(Duration::from_millis(25000)).await;
+                        // signal请求本身已经按策略重试，仍然失败时视为与server的连接已丢失
                         if let Err(e) = maintain.single_signal(30000).await {
-                            eprintln!("Maintaining component cannot send single signal: {}", e);
+                            let _ = sender.send(Option::Some(format!("Cannot send signal to server. {}", e)));
+                            break
                         }
                     }
                 });
-    
+
                 Result::Ok(())
             }else{
                 Result::Err(Box::new(ApplicationError::new("Check connection failed: timed out.")))
             }
         }else{
-            let host = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().host.clone();
-            let token = client_option.as_ref().unwrap().login_option.remote.as_ref().unwrap().token.clone();
+            let (host, token) = self.remote_login(client_option.as_ref().unwrap())?;
             self.set_access_remote(host, token);
             Result::Ok(())
         }
     }
-    pub async fn permanent(&self, enable: bool) {
+    pub async fn permanent(&self, enable: bool) -> Result<(), Box<dyn Error>> {
         let body = serde_json::json!({
             "type": "command-line-application",
             "value": enable
         });
-        match self.req_with_body(Method::POST, "/app/lifetime/permanent", body).await {
-            Ok(d) => {
-                let _: Vec<String> = d;
-            },
-            Err(e) => panic!("Error occurred when set permanent. {}", e)
-        }
+        let _: Vec<String> = self.request(Method::POST, "/app/lifetime/permanent").body(body).retry().fetch().await?;
+        Result::Ok(())
     }
     pub fn kill(&self) -> Result<(), Box<dyn Error>> {
-        if self.read_client_option().is_some_and(|c| c.login_option.mode == "remote") {
+        if self.read_client_option()?.is_some_and(|c| c.login_option.mode == "remote") {
             return Result::Err(Box::new(ApplicationError::new("This channel is runnning in remote mode. Cannot kill remote server.")))
         }
-        let pid_file = self.read_pid_file();
-        if let Some(pid_file) = pid_file {
-            let s = System::new_all();
-            if let Some(process) = s.process(Pid::from(pid_file.pid as usize)) {
-                if process.kill_with(Signal::Kill).is_none() {
-                    panic!("This signal isn't supported on this platform.")
+        // PID已被其他程序复用时不能结束它，只清理PID文件
+        match self.process_state()? {
+            ProcessState::Alive(pid) => {
+                let s = System::new_all();
+                if let Some(process) = s.process(Pid::from(pid as usize)) {
+                    if process.kill_with(Signal::Kill).is_none() {
+                        return Result::Err(Box::new(HedgeError::new("This signal isn't supported on this platform.").hint(&format!("Stop process {} manually.", pid))))
+                    }
                 }
-            }
+            },
+            ProcessState::Absent => {},
+            _ => self.remove_pid_file()?
         }
-        return Result::Ok(())
+        Result::Ok(())
     }
-    pub fn log(&self) {
-        let log_path = self.appdata_path.join("channel").join(&self.channel).join("server.log");
-        match fs::File::open(&log_path) {
-            Err(e) => if e.kind() == std::io::ErrorKind::NotFound {
-                return
-            }else{
-                panic!("Read log file {} failed. {}", log_path.to_str().unwrap(), e)
-            },
-            Ok(b) => {
-                let buf = BufReader::new(b);
-                for line in buf.lines() {
-                    println!("{}", line.unwrap());
-                }
+    // 根据PID文件与进程表判断server进程的状态
+    pub fn process_state(&self) -> Result<ProcessState, HedgeError> {
+        match self.read_pid_file()? {
+            None => Result::Ok(ProcessState::Absent),
+            Some(p) => Result::Ok(process_state_of(p.pid, &self.server_path, &self.server_command().channel_path))
+        }
+    }
+    // 进程已退出或PID已被复用时，清理PID文件，以便重新启动
+    pub fn clear_stale_pid_file(&self) -> Result<(), HedgeError> {
+        if let ProcessState::Dead(_) | ProcessState::Foreign(_) = self.process_state()? {
+            self.remove_pid_file()?;
+        }
+        Result::Ok(())
+    }
+    // 请求server正常退出：发送SIGTERM，等待PID文件消失；超时后升级为SIGKILL，并清理残留的PID文件
+    pub async fn shutdown(&self, timeout: Duration) -> Result<ShutdownResult, HedgeError> {
+        if self.read_client_option()?.is_some_and(|c| c.login_option.mode == "remote") {
+            return Result::Err(HedgeError::new("This channel is runnning in remote mode. Cannot stop remote server."))
+        }
+        let pid_file = match self.read_pid_file()? {
+            Some(p) => p,
+            None => return Result::Ok(ShutdownResult::NotRunning)
+        };
+        // 进程已退出或PID已被其他程序复用时，只清理PID文件，不发送信号
+        if !matches!(self.process_state()?, ProcessState::Alive(_)) {
+            self.remove_pid_file()?;
+            return Result::Ok(ShutdownResult::NotRunning)
+        }
+        let pid = Pid::from(pid_file.pid as usize);
+        let mut s = System::new();
+        s.refresh_process(pid);
+        if s.process(pid).and_then(|p| p.kill_with(Signal::Term)).is_none() {
+            return Result::Err(HedgeError::new("This signal isn't supported on this platform.").hint(&format!("Stop process {} manually.", pid_file.pid)))
+        }
+        let start = std::time::Instant::now();
+        while start.elapsed() < timeout {
+            async_std::task::sleep(Duration::from_millis(200)).await;
+            if self.read_pid_file()?.is_none() {
+                return Result::Ok(ShutdownResult::Graceful)
+            }
+            // 进程已退出但没有删除PID文件时，代为清理
+            if !s.refresh_process(pid) {
+                self.remove_pid_file()?;
+                return Result::Ok(ShutdownResult::Graceful)
+            }
+        }
+        if let Some(process) = s.process(pid) {
+            process.kill_with(Signal::Kill);
+        }
+        for _ in 0..25 {
+            async_std::task::sleep(Duration::from_millis(200)).await;
+            if !s.refresh_process(pid) {
+                break
             }
         }
+        self.remove_pid_file()?;
+        Result::Ok(ShutdownResult::Killed)
+    }
+    pub async fn log(&self, options: &LogOptions) -> Result<(), HedgeError> {
+        let log_path = self.appdata_path.join("channel").join(&self.channel).join("server.log");
+        print_log(&log_path, options).await.context(&format!("Read log file {} failed", log_path.to_string_lossy()))
     }
     async fn single_signal(&self, interval: i64) -> Result<(), Box<dyn Error>> {
         let body = serde_json::json!({
             "interval": interval,
             "standalone": true
         });
-        match self.req_without_res(Method::POST, "/app/lifetime/signal", body).await {
-            Ok(_) => Result::Ok(()),
-            Err(e) => Result::Err(e)
-        }
+        self.request(Method::POST, "/app/lifetime/signal").body(body).retry().execute().await
     }
-    fn start_server(&self) {
-        let bin_path = self.server_path.join("bin/hedge-v3-server");
+    // server的启动命令。start_server与systemd unit使用相同的命令
+    pub fn server_command(&self) -> ServerCommand {
         let channel_path = self.appdata_path.join("channel").join(&self.channel);
-        let args = ["--channel-path", channel_path.to_str().unwrap()];
+        ServerCommand {
+            bin_path: self.server_path.join("bin/hedge-v3-server"),
+            log_path: channel_path.join("server.log"),
+            channel_path
+        }
+    }
+    // 运行中的server仍在写入server.log，此时不能轮转
+    pub fn rotate_log(&self) -> Result<(), HedgeError> {
+        if let ProcessState::Alive(pid) = self.process_state()? {
+            return Result::Err(HedgeError::new(&format!("Server process {} is running.", pid)).hint("Stop it by 'hedge server stop --now' first."))
+        }
+        let log_path = self.server_command().log_path;
+        rotate_log(&log_path, self.log_retention).context(&format!("Cannot rotate server log {}", log_path.to_string_lossy()))
+    }
+    fn start_server(&self) -> Result<(), HedgeError> {
+        let ServerCommand { bin_path, channel_path, log_path } = self.server_command();
+        let args = [std::ffi::OsStr::new("--channel-path"), channel_path.as_os_str()];
         
-        let log_path = channel_path.join("server.log");
-        let out_file = fs::File::create(&log_path).unwrap();
-        let err_file = fs::File::create(&log_path).unwrap();
+        let log_context = format!("Cannot create server log {}", log_path.to_string_lossy());
+        rotate_log(&log_path, self.log_retention).context(&format!("Cannot rotate server log {}", log_path.to_string_lossy())).hint("Check whether the channel exists and is writable.")?;
+        // stdout与stderr共用同一个文件句柄，两者的输出都追加在文件末尾，不会互相覆盖
+        let out_file = fs::File::create(&log_path).context(&log_context).hint("Check whether the channel exists and is writable.")?;
+        let err_file = out_file.try_clone().context(&log_context)?;
         let stdout = Stdio::from(out_file);
         let stderr = Stdio::from(err_file);
 
-        Command::new(bin_path)
-            .args(args)
-            .stdout(stdout)
-            .stderr(stderr)
-            .spawn()
-            .unwrap();
+        match Command::new(&bin_path).args(args).stdout(stdout).stderr(stderr).spawn() {
+            Ok(mut child) => {
+                // 回收子进程，避免长时间运行的CLI(例如supervise)中server退出后残留僵尸进程
+                std::thread::spawn(move || child.wait());
+                Result::Ok(())
+            },
+            Err(e) => {
+                let hint = if e.kind() == std::io::ErrorKind::NotFound { "Server is not found. Check work_path.server_path in config." }else{ "Check the permission of the server executable." };
+                Result::Err(HedgeError::from(e).context(&format!("Cannot start server {}", bin_path.to_string_lossy())).hint(hint))
+            }
+        }
     }
-    async fn check_connection(&mut self) -> bool {
+    async fn check_connection(&mut self) -> Result<bool, HedgeError> {
         for _ in 0..100 {
             async_std::task::sleep(Duration::from_millis(100)).await;
 
-            let pid_file = self.read_pid_file();
+            let pid_file = self.read_pid_file()?;
             if pid_file.is_none() {
                 continue
             }
 
             let pid_file = pid_file.unwrap();
+            // server已退出时不必等到超时
+            match self.process_state()? {
+                ProcessState::Dead(pid) => return Result::Err(HedgeError::new(&format!("Server process {} exited unexpectedly.", pid)).hint("Run 'hedge server log' to see what happened.")),
+                ProcessState::Foreign(pid) => return Result::Err(HedgeError::new(&format!("PID {} in PID file belongs to another program.", pid)).hint("Run 'hedge server start' again to clean up the stale PID file.")),
+                _ => {}
+            }
             if pid_file.port.is_none() || pid_file.token.is_none() {
                 continue
             }
 
             self.set_access(pid_file.port.unwrap(), pid_file.token.unwrap());
-            match self.req(Method::GET, "/app/health").await {
-                Err(_) => {},
+            match self.health().await {
+                Err(_) => if let Some(conflict) = pid_file.port.and_then(|port| port_conflict(port, pid_file.pid)) {
+                    return Result::Err(HedgeError::new(&conflict).hint("Stop that process, or run 'hedge server restart' to let server choose another port."))
+                },
                 Ok(data) => {
                     let d: AppStatusRes = data;
                     if d.status == "READY" {
-                        return true
+                        return Result::Ok(true)
                     }
                 }
             }
         }
-        false
+        Result::Ok(false)
+    }
+    // 健康检查本身会被轮询，因此不重试，失败时立即返回
+    async fn health(&self) -> Result<AppStatusRes, Box<dyn Error>> {
+        self.request(Method::GET, "/app/health").no_retry().fetch().await
     }
     fn set_access(&mut self, port: i32, token: String) {
         self.access.address = Option::Some(format!("http://{}:{}", "localhost", port));
         self.access.token = Option::Some(token);
     }
-    fn set_access_remote(&mut self, host: String, token: String) {
-        self.access.address = Option::Some(format!("http://{}", host));
+    pub fn set_access_remote(&mut self, host: String, token: String) {
+        self.access.address = Option::Some(remote_address(&host));
         self.access.token = Option::Some(token);
         self.access.remote_mode = true;
     }
-    pub async fn req<U, T>(&self, method: Method, path: U) -> Result<T, Box<dyn std::error::Error>> where U: IntoUrl, T: serde::de::DeserializeOwned {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let mut b = self.client.request(method, url);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        let res = b.send().await?;
-        if res.status().is_success() || res.status().is_redirection() {
-            let text = res.text().await?;
-            Result::Ok(serde_json::from_str(&text)?)
-        }else{
-            let text = res.text().await?;
-            let err: ErrorResult = serde_json::from_str(&text)?;
-            Result::Err(Box::new(ApiResultError::new(&err.code, &err.message)))
-        }
+    // 检查server是否可以连接。不检查token
+    pub async fn check_health(&self) -> Result<(), Box<dyn Error>> {
+        self.health().await?;
+        Result::Ok(())
     }
-    pub async fn req_with_query<U, T>(&self, method: Method, path: U, query: &Vec<(&str, String)>) -> Result<T, Box<dyn std::error::Error>> where U: IntoUrl, T: serde::de::DeserializeOwned {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let mut b = self.client.request(method, url).query(query);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        let res = b.send().await?;
-        if res.status().is_success() || res.status().is_redirection() {
-            let text = res.text().await?;
-            Result::Ok(serde_json::from_str(&text)?)
-        }else{
-            let text = res.text().await?;
-            let err: ErrorResult = serde_json::from_str(&text)?;
-            Result::Err(Box::new(ApiResultError::new(&err.code, &err.message)))
-        }
+    // 检查token能否通过鉴权。任选一个需要鉴权的只读接口，不关心响应内容
+    pub async fn check_auth(&self) -> Result<(), Box<dyn Error>> {
+        self.request(Method::GET, "/api/setting/meta").no_retry().execute().await
     }
-    pub async fn req_with_body<U, T>(&self, method: Method, path: U, body: serde_json::Value) -> Result<T, Box<dyn std::error::Error>> where U: IntoUrl, T: serde::de::DeserializeOwned {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let body = serde_json::to_string(&body)?;
-        let mut b = self.client.request(method, url).body(body);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        let res = b.send().await?;
-        if res.status().is_success() || res.status().is_redirection() {
-            let text = res.text().await?;
-            Result::Ok(serde_json::from_str(&text)?)
-        }else{
-            let text = res.text().await?;
-            let err: ErrorResult = serde_json::from_str(&text)?;
-            Result::Err(Box::new(ApiResultError::new(&err.code, &err.message)))
-        }
+    // 读取当前channel的登录信息。credentials文件中的token优先于client.dat
+    pub fn login_info(&self) -> Result<LoginInfo, HedgeError> {
+        let client_option = match self.read_client_option()? {
+            None => return Result::Ok(LoginInfo { channel: self.channel.clone(), mode: "none".to_string(), host: Option::None, token: Option::None, token_source: Option::None }),
+            Some(c) => c
+        };
+        let remote = client_option.login_option.remote.as_ref();
+        let (token, token_source) = match self.credentials.get(&self.channel)? {
+            Some(t) => (Option::Some(t), Option::Some("credentials")),
+            None => match remote.map(|r| r.token.clone()).filter(|t| !t.is_empty()) {
+                Some(t) => (Option::Some(t), Option::Some("client.dat")),
+                None => (Option::None, Option::None)
+            }
+        };
+        Result::Ok(LoginInfo { channel: self.channel.clone(), mode: client_option.login_option.mode.clone(), host: remote.map(|r| r.host.clone()), token, token_source })
     }
-    pub async fn req_with_form<U, T>(&self, method: Method, path: U, form: reqwest::multipart::Form) -> Result<T, Box<dyn std::error::Error>> where U: IntoUrl, T: serde::de::DeserializeOwned {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let mut b = self.client.request(method, url).multipart(form);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        let res = b.send().await?;
-        if res.status().is_success() || res.status().is_redirection() {
-            let text = res.text().await?;
-            Result::Ok(serde_json::from_str(&text)?)
+    // 将远程登录信息写入client.dat。separate时token写入credentials文件，client.dat中只保留host
+    pub fn save_login(&self, host: &str, token: &str, separate: bool) -> Result<(), HedgeError> {
+        if separate {
+            self.credentials.set(&self.channel, token).context("Cannot save token")?;
+            self.write_client_login(host, "")
         }else{
-            let text = res.text().await?;
-            let err: ErrorResult = serde_json::from_str(&text)?;
-            Result::Err(Box::new(ApiResultError::new(&err.code, &err.message)))
+            // 避免credentials文件中的旧token覆盖新登录的token
+            self.credentials.remove(&self.channel)?;
+            self.write_client_login(host, token)
         }
     }
-    pub async fn req_without_res<U>(&self, method: Method, path: U, body: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> where U: IntoUrl {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let body = serde_json::to_string(&body)?;
-        let mut b = self.client.request(method, url).body(body);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        let res = b.send().await?;
-        if res.status().is_success() || res.status().is_redirection() {
-            Result::Ok(())
-        }else{
-            let text = res.text().await?;
-            let err: ErrorResult = serde_json::from_str(&text)?;
-            Result::Err(Box::new(ApiResultError::new(&err.code, &err.message)))
+    // 清除当前channel的token，返回是否确实清除了什么
+    pub fn logout(&self) -> Result<bool, HedgeError> {
+        let removed_credential = self.credentials.remove(&self.channel)?;
+        let removed_client = match self.read_client_option()? {
+            Some(c) if c.login_option.mode == "remote" && c.login_option.remote.as_ref().is_some_and(|r| !r.token.is_empty()) => {
+                self.write_client_login(&c.login_option.remote.unwrap().host, "")?;
+                true
+            },
+            _ => false
+        };
+        Result::Ok(removed_credential || removed_client)
+    }
+    pub fn request(&self, method: Method, path: &str) -> ServerRequest<'_> {
+        self.access.request(&self.requester, method, path)
+    }
+    // 按offset/limit分页请求列表接口，直到取得全部结果
+    pub async fn list_all<T: DeserializeOwned>(&self, path: &str) -> Result<Vec<T>, Box<dyn Error>> {
+        let mut ret: Vec<T> = Vec::new();
+        loop {
+            let query = vec![("offset", ret.len().to_string()), ("limit", LIST_ALL_LIMIT.to_string())];
+            let r: ListResult<T> = self.request(Method::GET, path).query(&query).fetch().await?;
+            let end = r.result.is_empty() || ret.len() + r.result.len() >= r.total as usize;
+            ret.extend(r.result);
+            if end {
+                return Result::Ok(ret)
+            }
         }
     }
-    fn read_client_option(&self) -> Option<ClientOption> {
-        let pid_file_path = self.appdata_path.join("channel").join(&self.channel).join("client.dat");
-        match fs::read_to_string(&pid_file_path) {
+    fn read_client_option(&self) -> Result<Option<ClientOption>, HedgeError> {
+        let client_file_path = self.appdata_path.join("channel").join(&self.channel).join("client.dat");
+        match fs::read_to_string(&client_file_path) {
             Err(e) => if e.kind() == std::io::ErrorKind::NotFound {
-                Option::None
+                Result::Ok(Option::None)
             }else{
-                panic!("Read pid file {} failed. {}", pid_file_path.to_str().unwrap(), e)
+                Result::Err(HedgeError::from(e).context(&format!("Read client file {} failed", client_file_path.to_string_lossy())))
             },
             Ok(s) => match serde_json::from_str(&s) {
-                Err(e) => panic!("Pid file {} format error. {}", pid_file_path.to_str().unwrap(), e),
-                Ok(d) => Option::Some(d)
+                Err(e) => Result::Err(HedgeError::format(&e.to_string())
+                    .context(&format!("Client file {} format error", client_file_path.to_string_lossy()))
+                    .hint("This file is written by Hedge app. Open this channel in Hedge app to rewrite it.")),
+                Ok(d) => Result::Ok(Option::Some(d))
             }
         }
     }
-    fn read_pid_file(&self) -> Option<PidFile> {
+    fn remote_login(&self, client_option: &ClientOption) -> Result<(String, String), HedgeError> {
+        let host = match &client_option.login_option.remote {
+            Some(r) => r.host.clone(),
+            None => return Result::Err(HedgeError::new("Remote host of this channel is not configured.").hint("Run 'hedge channel login --host <host>' to configure it."))
+        };
+        let token = match self.credentials.get(&self.channel)? {
+            Some(t) => t,
+            None => client_option.login_option.remote.as_ref().map(|r| r.token.clone()).unwrap_or_default()
+        };
+        if token.is_empty() {
+            return Result::Err(HedgeError::new(&format!("Not logged in to remote server {}.", host)).hint("Run 'hedge channel login --host <host>' to log in."))
+        }
+        Result::Ok((host, token))
+    }
+    // 只修改loginOption中的mode与remote，保留Hedge app写入的其他内容
+    fn write_client_login(&self, host: &str, token: &str) -> Result<(), HedgeError> {
+        let channel_path = self.appdata_path.join("channel").join(&self.channel);
+        let client_file_path = channel_path.join("client.dat");
+        let mut client: serde_json::Value = match fs::read_to_string(&client_file_path) {
+            Err(e) => if e.kind() == std::io::ErrorKind::NotFound {
+                serde_json::json!({})
+            }else{
+                return Result::Err(HedgeError::from(e).context(&format!("Read client file {} failed", client_file_path.to_string_lossy())))
+            },
+            Ok(s) => serde_json::from_str(&s).map_err(|e| HedgeError::format(&e.to_string())).context(&format!("Client file {} format error", client_file_path.to_string_lossy()))?
+        };
+        let login_option = match client.as_object_mut() {
+            Some(o) => o.entry("loginOption").or_insert_with(|| serde_json::json!({})),
+            None => return Result::Err(HedgeError::format("root is not an object").context(&format!("Client file {} format error", client_file_path.to_string_lossy())))
+        };
+        match login_option.as_object_mut() {
+            Some(o) => {
+                o.insert("mode".to_string(), serde_json::json!("remote"));
+                o.insert("remote".to_string(), serde_json::json!({ "host": host, "token": token }));
+            },
+            None => *login_option = serde_json::json!({ "mode": "remote", "remote": { "host": host, "token": token } })
+        }
+        fs::create_dir_all(&channel_path).context(&format!("Cannot create channel dir {}", channel_path.to_string_lossy()))?;
+        fs::write(&client_file_path, client.to_string()).context(&format!("Cannot write client file {}", client_file_path.to_string_lossy()))?;
+        Result::Ok(())
+    }
+    // 进程已不存在时残留的PID文件会让启动流程误认为server在运行
+    fn remove_pid_file(&self) -> Result<(), HedgeError> {
+        let pid_file_path = self.appdata_path.join("channel").join(&self.channel).join("server").join("PID");
+        match fs::remove_file(&pid_file_path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Result::Err(HedgeError::from(e).context(&format!("Cannot remove pid file {}", pid_file_path.to_string_lossy()))),
+            _ => Result::Ok(())
+        }
+    }
+    fn read_pid_file(&self) -> Result<Option<PidFile>, HedgeError> {
         let pid_file_path = self.appdata_path.join("channel").join(&self.channel).join("server").join("PID");
         match fs::read_to_string(&pid_file_path) {
             Err(e) => if e.kind() == std::io::ErrorKind::NotFound {
-                Option::None
+                Result::Ok(Option::None)
             }else{
-                panic!("Read pid file {} failed. {}", pid_file_path.to_str().unwrap(), e)
+                Result::Err(HedgeError::from(e).context(&format!("Read pid file {} failed", pid_file_path.to_string_lossy())))
             },
             Ok(s) => match serde_json::from_str(&s) {
-                Err(e) => panic!("Pid file {} format error. {}", pid_file_path.to_str().unwrap(), e),
-                Ok(d) => Option::Some(d)
+                Err(e) => Result::Err(HedgeError::format(&e.to_string())
+                    .context(&format!("Pid file {} format error", pid_file_path.to_string_lossy()))
+                    .hint("The server may be still writing it, try again later. If the server is not running, delete this file.")),
+                Ok(d) => Result::Ok(Option::Some(d))
             }
         }
     }
 }
 
+// 远程host可以是"host:port"，也可以是带协议与base path的完整URL(例如反向代理后的"https://example.com/hedge")
+fn remote_address(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    }else{
+        format!("http://{}", host)
+    }
+}
+
+// 端口被server以外的进程监听时，返回冲突的说明。通过/proc查找监听端口的socket及持有它的进程，仅支持Linux
+#[cfg(target_os = "linux")]
+fn port_conflict(port: i32, server_pid: i64) -> Option<String> {
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"].iter()
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .flat_map(|table| table.lines().skip(1).filter_map(|line| {
+            // 各列依次为sl、local_address、rem_address、st，第10列为inode。st为0A表示LISTEN
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let local_port = i32::from_str_radix(columns.get(1)?.rsplit(':').next()?, 16).ok()?;
+            (local_port == port && columns.get(3) == Some(&"0A")).then(|| format!("socket:[{}]", columns.get(9).unwrap_or(&"")))
+        }).collect::<Vec<_>>())
+        .collect();
+    if inodes.is_empty() {
+        return Option::None
+    }
+    let mut holder = Option::None;
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let pid: i64 = match entry.file_name().to_str().and_then(|n| n.parse().ok()) { Some(p) => p, None => continue };
+        let fds = match fs::read_dir(entry.path().join("fd")) { Ok(f) => f, Err(_) => continue };
+        if fds.flatten().any(|fd| fs::read_link(fd.path()).is_ok_and(|l| inodes.iter().any(|i| l.as_os_str() == i.as_str()))) {
+            if pid == server_pid {
+                return Option::None
+            }
+            holder = Option::Some(pid);
+        }
+    }
+    // 无权限读取其他用户的进程时，只能确定端口被占用
+    match holder {
+        Some(pid) => Option::Some(format!("Port {} is used by another process {}.", port, pid)),
+        None => fs::read_dir(format!("/proc/{}/fd", server_pid)).is_ok().then(|| format!("Port {} is used by another process.", port))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn port_conflict(_port: i32, _server_pid: i64) -> Option<String> {
+    Option::None
+}
+
 struct MaintainComponent {
-    client: Arc<reqwest::Client>,
+    requester: Requester,
     access: Access
 }
 
@@ -361,20 +575,7 @@ impl MaintainComponent {
             "interval": interval,
             "standalone": true
         });
-        match self.req_without_res(Method::POST, "/app/lifetime/signal", body).await {
-            Ok(_) => Result::Ok(()),
-            Err(e) => Result::Err(e)
-        }
-    }
-    pub async fn req_without_res<U>(&self, method: Method, path: U, body: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> where U: IntoUrl {
-        let url = self.access.address.as_ref().map(|address| format!("{}{}", address, path.as_str())).unwrap_or_else(|| path.as_str().to_string());
-        let body = serde_json::to_string(&body)?;
-        let mut b = self.client.request(method, url).body(body);
-        if let Some(token) = &self.access.token {
-            b = b.header("Authorization", format!("Bearer {}", token));
-        }
-        b.send().await?;
-        Result::Ok(())
+        self.access.request(&self.requester, Method::POST, "/app/lifetime/signal").body(body).retry().execute().await
     }
 }
 
@@ -394,9 +595,59 @@ struct ClientOptionLogin {
 #[derive(Deserialize)]
 struct ClientOptionLoginRemote {
     host: String,
+    #[serde(default)]
     token: String
 }
 
+// 判断PID对应进程的状态。channel模块检查其他channel的server时也使用它
+pub fn process_state_of(pid: i64, server_path: &Path, channel_path: &Path) -> ProcessState {
+    let mut s = System::new();
+    let sys_pid = Pid::from(pid as usize);
+    match s.refresh_process(sys_pid).then(|| s.process(sys_pid)).flatten() {
+        Some(process) if process.status() == ProcessStatus::Zombie => ProcessState::Dead(pid),
+        Some(process) if !is_server_process(process, server_path, channel_path) => ProcessState::Foreign(pid),
+        Some(_) => ProcessState::Alive(pid),
+        None => ProcessState::Dead(pid)
+    }
+}
+
+// server可能由启动脚本拉起(进程名为java等)，因此按可执行文件与启动参数判断
+fn is_server_process(process: &sysinfo::Process, server_path: &Path, channel_path: &Path) -> bool {
+    let channel_path = channel_path.to_string_lossy();
+    process.exe().starts_with(server_path) || process.cmd().iter().any(|arg| arg.contains("hedge-v3-server") || *arg == channel_path)
+}
+
+pub struct ServerCommand {
+    pub bin_path: PathBuf,
+    pub channel_path: PathBuf,
+    pub log_path: PathBuf
+}
+
+#[derive(PartialEq, Eq)]
+pub enum ProcessState {
+    // 没有PID文件
+    Absent,
+    // PID文件对应的进程在运行
+    Alive(i64),
+    // PID文件残留，但进程已不存在
+    Dead(i64),
+    // PID文件残留，且PID已被其他程序的进程复用
+    Foreign(i64)
+}
+
+#[derive(PartialEq, Eq)]
+pub enum ShutdownResult {
+    NotRunning, Graceful, Killed
+}
+
+pub struct LoginInfo {
+    pub channel: String,
+    pub mode: String,
+    pub host: Option<String>,
+    pub token: Option<String>,
+    pub token_source: Option<&'static str>
+}
+
 #[derive(Deserialize)]
 struct PidFile {
     pid: i64,
@@ -412,12 +663,21 @@ pub struct ServerStatus {
     pub pid: Option<i64>,
     pub host: Option<String>,
     pub port: Option<i32>,
-    pub start_time: Option<i64>
+    pub start_time: Option<i64>,
+    // Crashed、Stale时的说明
+    pub detail: Option<String>
 }
 
 #[derive(PartialEq, Eq, Deserialize)]
 pub enum ServerStatusType {
-    NotInitialized, Stop, Starting, Loading, Running, ConnectionError
+    NotInitialized, Stop, Starting, Loading, Running, ConnectionError, Crashed, Stale
+}
+
+impl ServerStatusType {
+    // server已不在运行。Crashed时PID文件仍残留，status不会清理它，由之后的start/stop/kill清理
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, Self::Stop | Self::Crashed)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -451,6 +711,8 @@ impl fmt::Display for ServerStatusType {
             Self::Loading => write!(f, "Loading"),
             Self::Running => write!(f, "Running"),
             Self::ConnectionError => write!(f, "ConnectionError"),
+            Self::Crashed => write!(f, "Crashed"),
+            Self::Stale => write!(f, "Stale"),
         }
     }
 }
@@ -0,0 +1,270 @@
+use std::{fs, io::{self, BufRead, BufReader, Seek}, path::{Path, PathBuf}, time::Duration, collections::VecDeque};
+use chrono::NaiveDateTime;
+
+// server日志级别，按严重程度排序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+pub enum LogLevel {
+    Trace, Debug, Info, Warn, Error
+}
+
+impl LogLevel {
+    fn parse(word: &str) -> Option<LogLevel> {
+        match word {
+            "TRACE" => Option::Some(LogLevel::Trace),
+            "DEBUG" => Option::Some(LogLevel::Debug),
+            "INFO" => Option::Some(LogLevel::Info),
+            "WARN" | "WARNING" => Option::Some(LogLevel::Warn),
+            "ERROR" | "FATAL" => Option::Some(LogLevel::Error),
+            _ => Option::None
+        }
+    }
+}
+
+pub struct LogOptions {
+    pub follow: bool,
+    pub lines: Option<usize>,
+    pub since: Option<NaiveDateTime>,
+    pub grep: Option<String>,
+    pub level: Option<LogLevel>
+}
+
+// 逐行过滤日志。没有时间与级别的行(例如异常堆栈)沿用上一行的时间与级别，因此会与所属的日志一起被保留或过滤
+struct LogFilter<'t> {
+    options: &'t LogOptions,
+    time: Option<NaiveDateTime>,
+    level: Option<LogLevel>
+}
+
+impl <'t> LogFilter<'t> {
+    fn new(options: &'t LogOptions) -> LogFilter<'t> {
+        LogFilter { options, time: Option::None, level: Option::None }
+    }
+    fn accept(&mut self, line: &str) -> bool {
+        if let Some(time) = parse_time(line) {
+            self.time = Option::Some(time);
+            self.level = parse_level(line);
+        }
+        if let Some(since) = &self.options.since {
+            if self.time.map(|t| t < *since).unwrap_or(true) {
+                return false
+            }
+        }
+        if let Some(level) = &self.options.level {
+            if self.level.map(|l| l < *level).unwrap_or(true) {
+                return false
+            }
+        }
+        if let Some(grep) = &self.options.grep {
+            if !line.contains(grep.as_str()) {
+                return false
+            }
+        }
+        true
+    }
+}
+
+// 日志行以"yyyy-MM-dd HH:mm:ss"开头(可带毫秒，日期与时间之间也可以是'T')
+fn parse_time(line: &str) -> Option<NaiveDateTime> {
+    let head = line.get(..19)?;
+    NaiveDateTime::parse_from_str(head, "%Y-%m-%d %H:%M:%S").or_else(|_| NaiveDateTime::parse_from_str(head, "%Y-%m-%dT%H:%M:%S")).ok()
+}
+
+// 在行首的几个词中寻找级别
+fn parse_level(line: &str) -> Option<LogLevel> {
+    line.split(|c: char| c.is_whitespace() || c == '[' || c == ']').filter(|w| !w.is_empty()).take(6).find_map(LogLevel::parse)
+}
+
+// 输出日志。follow时持续等待新内容；日志被截断或被轮转为新文件时，从新文件的开头继续
+pub async fn print_log(log_path: &Path, options: &LogOptions) -> Result<(), io::Error> {
+    let mut filter = LogFilter::new(options);
+    let mut reader = match fs::File::open(log_path) {
+        Ok(f) => BufReader::new(f),
+        Err(e) => if e.kind() == io::ErrorKind::NotFound && options.follow {
+            wait_for_file(log_path).await?
+        }else if e.kind() == io::ErrorKind::NotFound {
+            return Result::Ok(())
+        }else{
+            return Result::Err(e)
+        }
+    };
+
+    // 首次读取时，-n只保留过滤后的最后几行。follow时不完整的最后一行留在line中，等写完后再输出
+    let mut tail: VecDeque<String> = VecDeque::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        if options.follow && !line.ends_with('\n') {
+            break
+        }
+        let content = line.trim_end_matches(['\n', '\r']);
+        if filter.accept(content) {
+            match options.lines {
+                Some(n) => {
+                    if n > 0 {
+                        if tail.len() == n {
+                            tail.pop_front();
+                        }
+                        tail.push_back(content.to_string());
+                    }
+                },
+                None => println!("{}", content)
+            }
+        }
+        line.clear();
+    }
+    for l in tail {
+        println!("{}", l);
+    }
+    if !options.follow {
+        return Result::Ok(())
+    }
+
+    let mut identity = file_identity(log_path);
+    loop {
+        print_complete_lines(&mut reader, &mut line, &mut filter)?;
+        async_std::task::sleep(Duration::from_millis(500)).await;
+        let position = reader.stream_position()?;
+        let current = file_identity(log_path);
+        let rotated = current != identity;
+        let truncated = !rotated && fs::metadata(log_path).map(|m| m.len() < position).unwrap_or(false);
+        if rotated || truncated {
+            if rotated {
+                // 轮转后旧句柄仍指向旧文件，先读完等待期间写入旧文件的内容。旧文件不会再有后续，剩余的不完整行也一并输出
+                print_complete_lines(&mut reader, &mut line, &mut filter)?;
+                if !line.is_empty() && filter.accept(line.trim_end_matches(['\n', '\r'])) {
+                    println!("{}", line.trim_end_matches(['\n', '\r']));
+                }
+            }
+            line.clear();
+            reader = wait_for_file(log_path).await?;
+            identity = file_identity(log_path);
+            filter = LogFilter::new(options);
+        }
+    }
+}
+
+// 读到当前末尾，输出其中完整的行。不完整的行留在line中，等待后续内容
+fn print_complete_lines(reader: &mut BufReader<fs::File>, line: &mut String, filter: &mut LogFilter) -> Result<(), io::Error> {
+    while reader.read_line(line)? > 0 {
+        if !line.ends_with('\n') {
+            break
+        }
+        let content = line.trim_end_matches(['\n', '\r']);
+        if filter.accept(content) {
+            println!("{}", content);
+        }
+        line.clear();
+    }
+    Result::Ok(())
+}
+
+async fn wait_for_file(log_path: &Path) -> Result<BufReader<fs::File>, io::Error> {
+    loop {
+        match fs::File::open(log_path) {
+            Ok(f) => return Result::Ok(BufReader::new(f)),
+            Err(e) => if e.kind() != io::ErrorKind::NotFound {
+                return Result::Err(e)
+            }
+        }
+        async_std::task::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+// 用于判断日志文件是否已被替换为新文件
+#[cfg(unix)]
+fn file_identity(log_path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(log_path).ok().map(|m| m.ino())
+}
+
+#[cfg(not(unix))]
+fn file_identity(log_path: &Path) -> Option<u64> {
+    fs::metadata(log_path).ok().and_then(|m| m.created().ok()).and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).map(|d| d.as_millis() as u64)
+}
+
+// 轮转日志：server.log依次改名为server.1.log ~ server.N.log，超出的部分删除
+pub fn rotate_log(log_path: &Path, retention: usize) -> Result<(), io::Error> {
+    if !log_path.exists() {
+        return Result::Ok(())
+    }
+    if retention == 0 {
+        return fs::remove_file(log_path)
+    }
+    let rotated = |i: usize| -> PathBuf { log_path.with_file_name(format!("server.{}.log", i)) };
+    let _ = fs::remove_file(rotated(retention));
+    for i in (1..retention).rev() {
+        let from = rotated(i);
+        if from.exists() {
+            fs::rename(from, rotated(i + 1))?;
+        }
+    }
+    fs::rename(log_path, rotated(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(since: Option<&str>, grep: Option<&str>, level: Option<LogLevel>) -> LogOptions {
+        let since = since.map(|s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap());
+        LogOptions { follow: false, lines: Option::None, since, grep: grep.map(|s| s.to_string()), level }
+    }
+
+    #[test]
+    fn parses_time_and_level_of_line() {
+        assert!(parse_time("2024-01-02 03:04:05.123 [main] INFO x").is_some());
+        assert!(parse_time("2024-01-02T03:04:05 WARN x").is_some());
+        assert!(parse_time("\tat Main.run(Main.kt:1)").is_none());
+        assert_eq!(parse_level("2024-01-02 03:04:05.123 [main] WARN c.h.Server - x"), Option::Some(LogLevel::Warn));
+        assert_eq!(parse_level("2024-01-02 03:04:05 ERROR x"), Option::Some(LogLevel::Error));
+    }
+
+    #[test]
+    fn continuation_lines_follow_their_entry() {
+        let options = options(Option::Some("2024-01-02 00:00:00"), Option::None, Option::Some(LogLevel::Warn));
+        let mut filter = LogFilter::new(&options);
+        let lines = [
+            "2024-01-01 23:59:59 ERROR before since",
+            "\tat stack.of.old",
+            "2024-01-02 00:00:01 INFO too low",
+            "2024-01-02 00:00:02 ERROR failed",
+            "\tat stack.of.failed"
+        ];
+        let accepted: Vec<&str> = lines.into_iter().filter(|l| filter.accept(l)).collect();
+        assert_eq!(accepted, vec!["2024-01-02 00:00:02 ERROR failed", "\tat stack.of.failed"]);
+    }
+
+    #[test]
+    fn grep_matches_each_line() {
+        let options = options(Option::None, Option::Some("needle"), Option::None);
+        let mut filter = LogFilter::new(&options);
+        assert!(filter.accept("2024-01-02 00:00:00 INFO a needle here"));
+        assert!(!filter.accept("2024-01-02 00:00:00 INFO nothing"));
+    }
+
+    #[test]
+    fn rotate_log_keeps_retention() {
+        let dir = std::env::temp_dir().join(format!("hedge-cli-test-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("server.log");
+        let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+
+        for i in 1..=4 {
+            fs::write(&log_path, format!("run {}", i)).unwrap();
+            rotate_log(&log_path, 2).unwrap();
+        }
+        assert!(!log_path.exists());
+        assert_eq!(read("server.1.log").as_deref(), Option::Some("run 4"));
+        assert_eq!(read("server.2.log").as_deref(), Option::Some("run 3"));
+        assert_eq!(read("server.3.log"), Option::None);
+
+        // 没有日志时什么也不做；retention为0时直接删除
+        rotate_log(&log_path, 2).unwrap();
+        assert_eq!(read("server.1.log").as_deref(), Option::Some("run 4"));
+        fs::write(&log_path, "run 5").unwrap();
+        rotate_log(&log_path, 0).unwrap();
+        assert!(!log_path.exists());
+        assert_eq!(read("server.1.log").as_deref(), Option::Some("run 4"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
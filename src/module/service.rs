@@ -0,0 +1,199 @@
+use std::{fs, path::{Path, PathBuf}, process::Command};
+use home::home_dir;
+use crate::utils::error::{HedgeError, ErrorContext};
+use super::server::ServerCommand;
+
+pub struct UnitFile {
+    pub name: String,
+    pub content: String
+}
+
+// 生成的systemd user unit。maintain为维护任务的service与timer，仅在指定了维护命令时生成
+pub struct ServiceUnits {
+    pub service: UnitFile,
+    pub maintain: Option<(UnitFile, UnitFile)>
+}
+
+impl ServiceUnits {
+    pub fn files(&self) -> Vec<&UnitFile> {
+        let mut files = vec![&self.service];
+        if let Some((service, timer)) = &self.maintain {
+            files.push(service);
+            files.push(timer);
+        }
+        files
+    }
+}
+
+pub struct ServiceOptions<'t> {
+    pub channel: &'t str,
+    pub cli_path: &'t Path,
+    // 指定了LOCAL_CONFIG_PATH时，unit中的CLI也需要读取同一份配置
+    pub config_path: Option<&'t Path>,
+    pub timer_command: Option<&'t str>,
+    pub timer_calendar: &'t str
+}
+
+pub fn unit_name(channel: &str) -> String {
+    format!("hedge-server-{}.service", channel)
+}
+
+// server进程由unit直接启动，命令与ServerManager::start_server相同。启动前由CLI轮转server.log，
+// 启动后由CLI等待server就绪并设置permanent模式；停止时systemd发送SIGTERM，server正常退出
+pub fn generate_units(command: &ServerCommand, options: &ServiceOptions) -> ServiceUnits {
+    let cli = quote(&options.cli_path.to_string_lossy());
+    let environment = options.config_path.map(|p| format!("Environment={}\n", quote(&format!("LOCAL_CONFIG_PATH={}", p.to_string_lossy())))).unwrap_or_default();
+    let service_name = unit_name(options.channel);
+    let service = format!("\
+[Unit]
+Description=Hedge backend server (channel {channel})
+After=network.target
+
+[Service]
+Type=simple
+{environment}ExecStartPre={cli} --channel {channel} server rotate-log
+ExecStart={bin} --channel-path {channel_path}
+ExecStartPost={cli} --channel {channel} server start --no-spawn
+StandardOutput=append:{log}
+StandardError=append:{log}
+Restart=on-failure
+RestartSec=5
+KillSignal=SIGTERM
+TimeoutStopSec=30
+
+[Install]
+WantedBy=default.target
+",
+        channel = options.channel,
+        environment = environment,
+        bin = quote(&command.bin_path.to_string_lossy()),
+        channel_path = quote(&command.channel_path.to_string_lossy()),
+        cli = cli,
+        log = escape_specifiers(&command.log_path.to_string_lossy())
+    );
+    let maintain = options.timer_command.map(|timer_command| {
+        let maintain_service = format!("\
+[Unit]
+Description=Hedge maintenance job (channel {channel})
+After={service_name}
+
+[Service]
+Type=oneshot
+{environment}ExecStart={cli} --channel {channel} {timer_command}
+",
+            channel = options.channel,
+            environment = environment,
+            service_name = service_name,
+            cli = cli,
+            timer_command = timer_command
+        );
+        let timer = format!("\
+[Unit]
+Description=Run Hedge maintenance job (channel {channel})
+
+[Timer]
+OnCalendar={calendar}
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+",
+            channel = options.channel,
+            calendar = options.timer_calendar
+        );
+        (UnitFile { name: format!("hedge-server-{}-maintain.service", options.channel), content: maintain_service }, UnitFile { name: format!("hedge-server-{}-maintain.timer", options.channel), content: timer })
+    });
+    ServiceUnits { service: UnitFile { name: service_name, content: service }, maintain }
+}
+
+pub fn user_unit_dir() -> Result<PathBuf, HedgeError> {
+    match home_dir() {
+        Some(h) => Result::Ok(h.join(".config/systemd/user")),
+        None => Result::Err(HedgeError::new("Cannot read HOME dir.").hint("Check the HOME environment variable."))
+    }
+}
+
+// 写入unit文件并重新加载systemd。enable时同时启用并启动unit，返回写入的文件
+pub fn install_units(units: &ServiceUnits, enable: bool) -> Result<Vec<PathBuf>, HedgeError> {
+    let dir = user_unit_dir()?;
+    fs::create_dir_all(&dir).context(&format!("Cannot create unit dir {}", dir.to_string_lossy()))?;
+    let mut written = Vec::new();
+    for unit in units.files() {
+        let path = dir.join(&unit.name);
+        fs::write(&path, &unit.content).context(&format!("Cannot write unit {}", path.to_string_lossy()))?;
+        written.push(path);
+    }
+    systemctl(&["daemon-reload"])?;
+    if enable {
+        systemctl(&["enable", "--now", &units.service.name])?;
+        if let Some((_, timer)) = &units.maintain {
+            systemctl(&["enable", "--now", &timer.name])?;
+        }
+    }
+    Result::Ok(written)
+}
+
+fn systemctl(args: &[&str]) -> Result<(), HedgeError> {
+    let command = format!("systemctl --user {}", args.join(" "));
+    match Command::new("systemctl").arg("--user").args(args).output() {
+        Err(e) => Result::Err(HedgeError::from(e).context(&format!("Cannot run {}", command)).hint("Check whether systemd is available on this system.")),
+        Ok(o) if !o.status.success() => Result::Err(HedgeError::new(String::from_utf8_lossy(&o.stderr).trim()).context(&format!("Failed to run {}", command))),
+        Ok(_) => Result::Ok(())
+    }
+}
+
+// 读取进程所属的cgroup，判断它是否由此channel生成的service管理，返回unit名称。
+// 由CLI在其他unit(例如维护任务、cron)中启动的server不算作由systemd管理
+pub fn systemd_unit(pid: i64, channel: &str) -> Option<String> {
+    let cgroup = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    let name = unit_name(channel);
+    in_cgroup(&cgroup, &name).then_some(name)
+}
+
+fn in_cgroup(cgroup: &str, unit: &str) -> bool {
+    cgroup.lines()
+        .filter_map(|line| line.rsplit(':').next())
+        .flat_map(|path| path.rsplit('/'))
+        .any(|segment| segment == unit)
+}
+
+// systemd的命令行参数中含有空格时需要加引号
+fn quote(s: &str) -> String {
+    let s = escape_specifiers(s);
+    if s.contains(char::is_whitespace) { format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")) }else{ s }
+}
+
+// unit文件中的'%'会被当作specifier展开。StandardOutput=append:之后的路径按原样读取到行尾，不支持引号，只需转义'%'
+fn escape_specifiers(s: &str) -> String {
+    s.replace('%', "%%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> ServerCommand {
+        ServerCommand { bin_path: PathBuf::from("/opt/hedge/bin/hedge-v3-server"), channel_path: PathBuf::from("/data/my channel"), log_path: PathBuf::from("/data/my channel/100%/server.log") }
+    }
+
+    #[test]
+    fn generate_units_rotates_log_and_escapes_paths() {
+        let options = ServiceOptions { channel: "default", cli_path: Path::new("/usr/bin/hedge"), config_path: Option::None, timer_command: Option::None, timer_calendar: "daily" };
+        let units = generate_units(&command(), &options);
+        let content = &units.service.content;
+        assert!(content.contains("ExecStartPre=/usr/bin/hedge --channel default server rotate-log\n"));
+        assert!(content.contains("ExecStart=/opt/hedge/bin/hedge-v3-server --channel-path \"/data/my channel\"\n"));
+        assert!(content.contains("StandardOutput=append:/data/my channel/100%%/server.log\n"));
+        assert!(units.maintain.is_none());
+    }
+
+    #[test]
+    fn in_cgroup_matches_unit_exactly() {
+        let cgroup = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/hedge-server-default.service\n";
+        assert!(in_cgroup(cgroup, "hedge-server-default.service"));
+        assert!(!in_cgroup(cgroup, "hedge-server-other.service"));
+        let maintain = "0::/user.slice/user-1000.slice/user@1000.service/app.slice/hedge-server-default-maintain.service\n";
+        assert!(!in_cgroup(maintain, "hedge-server-default.service"));
+        assert!(!in_cgroup("0::/system.slice/cron.service\n", "hedge-server-default.service"));
+    }
+}
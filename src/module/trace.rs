@@ -0,0 +1,117 @@
+use std::{fs, io::{self, Write}, path::{Path, PathBuf}, sync::Mutex};
+use reqwest::header::AUTHORIZATION;
+use crate::utils::error::{HedgeError, ErrorContext};
+use super::{config::LocalConfig, request::{Exchange, Middleware}};
+
+// 单个日志文件的最大尺寸，超过时轮转
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+// 保留的历史日志文件数量
+const MAX_LOG_FILES: usize = 5;
+// body摘要的最大长度
+const BODY_EXCERPT_LEN: usize = 500;
+
+// 请求追踪的输出位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceTarget {
+    Stderr,
+    File
+}
+
+impl TraceTarget {
+    // 解析环境变量HEDGE_CLI_LOG的值
+    pub fn parse(value: &str) -> Result<Option<TraceTarget>, HedgeError> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "0" | "off" => Result::Ok(Option::None),
+            "1" | "stderr" => Result::Ok(Option::Some(TraceTarget::Stderr)),
+            "file" => Result::Ok(Option::Some(TraceTarget::File)),
+            _ => Result::Err(HedgeError::format(&format!("Invalid value '{}' of HEDGE_CLI_LOG", value)).hint("Available values: stderr, file, off."))
+        }
+    }
+}
+
+// 将每一次请求尝试写入日志的中间件。Authorization头会被脱敏
+pub struct Tracer {
+    writer: Mutex<Box<dyn Write + Send>>
+}
+
+impl Tracer {
+    pub fn new(config: &LocalConfig, target: TraceTarget) -> Result<Tracer, HedgeError> {
+        let writer: Box<dyn Write + Send> = match target {
+            TraceTarget::Stderr => Box::new(io::stderr()),
+            TraceTarget::File => {
+                let dir = config.work_path.userdata_path.join("cli/logs");
+                Box::new(open_log_file(&dir).context(&format!("Cannot open trace log in {}", dir.to_string_lossy())).hint("Check the permission of this directory, or use HEDGE_CLI_LOG=stderr.")?)
+            }
+        };
+        Result::Ok(Tracer { writer: Mutex::new(writer) })
+    }
+}
+
+impl Middleware for Tracer {
+    fn after(&self, exchange: &Exchange) {
+        let mut lines = vec![format!("[{}] {} {} (attempt {})", chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"), exchange.method, exchange.url, exchange.attempt + 1)];
+        for (name, value) in exchange.headers {
+            let value = if name == AUTHORIZATION { redact(value.to_str().unwrap_or("")) }else{ value.to_str().unwrap_or("<binary>").to_string() };
+            lines.push(format!("  > {}: {}", name, value));
+        }
+        if let Some(body) = exchange.request_body {
+            lines.push(format!("  > {}", excerpt(body)));
+        }
+        let elapsed = exchange.elapsed.as_millis();
+        match (exchange.status, exchange.error) {
+            (_, Some(e)) => lines.push(format!("  < error after {}ms: {}", elapsed, e)),
+            (Some(status), None) => lines.push(format!("  < {} in {}ms", status, elapsed)),
+            (None, None) => lines.push(format!("  < no response in {}ms", elapsed))
+        }
+        if let Some(body) = exchange.body {
+            lines.push(format!("  < {}", excerpt(body)));
+        }
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writeln!(writer, "{}", lines.join("\n"));
+            let _ = writer.flush();
+        }
+    }
+}
+
+// 只保留鉴权方式，隐藏凭证本身
+fn redact(value: &str) -> String {
+    match value.split_once(' ') {
+        Some((scheme, _)) => format!("{} ***", scheme),
+        None => "***".to_string()
+    }
+}
+
+fn excerpt(body: &str) -> String {
+    let body = body.trim();
+    if body.chars().count() > BODY_EXCERPT_LEN {
+        format!("{}...({} bytes)", body.chars().take(BODY_EXCERPT_LEN).collect::<String>(), body.len())
+    }else{
+        body.to_string()
+    }
+}
+
+// 打开trace.log用于追加。当前文件超过尺寸上限时，依次轮转为trace.1.log ~ trace.N.log
+fn open_log_file(dir: &Path) -> Result<fs::File, io::Error> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join("trace.log");
+    if fs::metadata(&path).map(|m| m.len() >= MAX_LOG_SIZE).unwrap_or(false) {
+        let rotated = |i: usize| -> PathBuf { dir.join(format!("trace.{}.log", i)) };
+        let _ = fs::remove_file(rotated(MAX_LOG_FILES));
+        for i in (1..MAX_LOG_FILES).rev() {
+            let _ = fs::rename(rotated(i), rotated(i + 1));
+        }
+        fs::rename(&path, rotated(1))?;
+    }
+    fs::OpenOptions::new().create(true).append(true).open(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_keeps_only_scheme() {
+        assert_eq!(redact("Bearer goodtoken12345"), "Bearer ***");
+        assert_eq!(redact("goodtoken12345"), "***");
+    }
+}
@@ -10,6 +10,14 @@ pub struct ApiResultError {
     pub message: String
 }
 
+// server返回了错误状态码，但响应不是server的错误结构(例如反向代理返回的页面)
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    pub status: u16,
+    pub reason: String,
+    pub body: String
+}
+
 impl ApplicationError {
     pub fn new(message: &str) -> ApplicationError {
         ApplicationError { message: message.to_string() }
@@ -22,6 +30,12 @@ impl ApiResultError {
     }
 }
 
+impl HttpStatusError {
+    pub fn new(status: u16, reason: &str, body: &str) -> HttpStatusError {
+        HttpStatusError { status, reason: reason.to_string(), body: body.to_string() }
+    }
+}
+
 impl std::fmt::Display for ApplicationError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.message)
@@ -34,6 +48,20 @@ impl std::fmt::Display for ApiResultError {
     }
 }
 
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // 响应体只截取开头一段，避免输出整个HTML页面
+        let excerpt: String = self.body.trim().chars().take(200).collect();
+        if excerpt.is_empty() {
+            write!(f, "HTTP {} {}", self.status, self.reason)
+        }else{
+            write!(f, "HTTP {} {}: {}", self.status, self.reason, excerpt)
+        }
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
 impl std::error::Error for ApplicationError {
     fn cause(&self) -> Option<&dyn std::error::Error> {
         Option::None
@@ -56,4 +84,116 @@ impl std::error::Error for ApiResultError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         Option::None
     }
-}
\ No newline at end of file
+}
+// crate统一的错误类型。Context在错误上附加正在进行的操作，Hint附加给用户的解决提示，二者层层包装形成错误链
+#[derive(Debug)]
+pub enum HedgeError {
+    Application(ApplicationError),
+    ApiResult(ApiResultError),
+    Io(std::io::Error),
+    Format(String),
+    Other(Box<dyn std::error::Error>),
+    Context { message: String, source: Box<HedgeError> },
+    Hint { hint: String, source: Box<HedgeError> }
+}
+
+impl HedgeError {
+    pub fn new(message: &str) -> HedgeError {
+        HedgeError::Application(ApplicationError::new(message))
+    }
+    pub fn format(message: &str) -> HedgeError {
+        HedgeError::Format(message.to_string())
+    }
+    pub fn context(self, message: &str) -> HedgeError {
+        HedgeError::Context { message: message.to_string(), source: Box::new(self) }
+    }
+    pub fn hint(self, hint: &str) -> HedgeError {
+        HedgeError::Hint { hint: hint.to_string(), source: Box::new(self) }
+    }
+    // 由外向内收集错误链上的全部提示
+    pub fn hints(&self) -> Vec<&str> {
+        let mut ret = Vec::new();
+        let mut current = self;
+        loop {
+            match current {
+                HedgeError::Context { source, .. } => current = source,
+                HedgeError::Hint { hint, source } => {
+                    ret.push(hint.as_str());
+                    current = source
+                },
+                _ => return ret
+            }
+        }
+    }
+    // 错误链最内层是否为格式错误。用于区分输入错误与其他错误
+    pub fn is_format(&self) -> bool {
+        match self {
+            HedgeError::Context { source, .. } | HedgeError::Hint { source, .. } => source.is_format(),
+            HedgeError::Format(_) => true,
+            _ => false
+        }
+    }
+}
+
+pub trait ErrorContext<T> {
+    fn context(self, message: &str) -> Result<T, HedgeError>;
+    fn hint(self, hint: &str) -> Result<T, HedgeError>;
+}
+
+impl <T, E: Into<HedgeError>> ErrorContext<T> for Result<T, E> {
+    fn context(self, message: &str) -> Result<T, HedgeError> {
+        self.map_err(|e| e.into().context(message))
+    }
+    fn hint(self, hint: &str) -> Result<T, HedgeError> {
+        self.map_err(|e| e.into().hint(hint))
+    }
+}
+
+impl From<ApplicationError> for HedgeError {
+    fn from(e: ApplicationError) -> Self {
+        HedgeError::Application(e)
+    }
+}
+
+impl From<ApiResultError> for HedgeError {
+    fn from(e: ApiResultError) -> Self {
+        HedgeError::ApiResult(e)
+    }
+}
+
+impl From<std::io::Error> for HedgeError {
+    fn from(e: std::io::Error) -> Self {
+        HedgeError::Io(e)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for HedgeError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        HedgeError::Other(e)
+    }
+}
+
+impl std::fmt::Display for HedgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HedgeError::Application(e) => write!(f, "{}", e),
+            HedgeError::ApiResult(e) => write!(f, "{}", e),
+            HedgeError::Io(e) => write!(f, "{}", e),
+            HedgeError::Format(message) => write!(f, "{}", message),
+            HedgeError::Other(e) => write!(f, "{}", e),
+            HedgeError::Context { message, source } => write!(f, "{}: {}", message, source),
+            HedgeError::Hint { source, .. } => write!(f, "{}", source)
+        }
+    }
+}
+
+impl std::error::Error for HedgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HedgeError::Io(e) => Option::Some(e),
+            HedgeError::Other(e) => Option::Some(e.as_ref()),
+            HedgeError::Context { source, .. } | HedgeError::Hint { source, .. } => Option::Some(source.as_ref()),
+            _ => Option::None
+        }
+    }
+}
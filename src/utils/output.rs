@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use super::error::HedgeError;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson
+}
+
+// 命令先构造结果模型，再交给Output渲染。text格式由命令提供的闭包渲染；
+// json格式在结束时输出一份完整的文档；ndjson格式每行输出一个{"type", "data"}对象，逐项的结果会在产生时立即输出。
+pub struct Output {
+    pub format: OutputFormat
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Output {
+        Output { format }
+    }
+    pub fn is_text(&self) -> bool {
+        self.format == OutputFormat::Text
+    }
+    // 只在text格式下输出的提示信息
+    pub fn message(&self, message: &str) {
+        if self.is_text() {
+            println!("{}", message)
+        }
+    }
+    // 过程中的警告，例如重试。text格式输出到stderr；ndjson格式输出一行warning；json格式只输出最终的文档，因此忽略
+    pub fn warning(&self, message: &str) {
+        match self.format {
+            OutputFormat::Text => eprintln!("{}", message),
+            OutputFormat::Ndjson => print_line("warning", &json!({ "message": message })),
+            OutputFormat::Json => {}
+        }
+    }
+    // 流式的一项。text与ndjson立即输出；json格式下由调用方收集，最后通过summary一并输出
+    pub fn item<T: Serialize>(&self, item: &T, text: impl FnOnce(&T)) {
+        match self.format {
+            OutputFormat::Text => text(item),
+            OutputFormat::Ndjson => print_line("item", item),
+            OutputFormat::Json => {}
+        }
+    }
+    // 各项已经通过item输出后，输出汇总
+    pub fn summary<T: Serialize, S: Serialize>(&self, items: &[T], summary: &S, text: impl FnOnce(&S)) {
+        match self.format {
+            OutputFormat::Text => text(summary),
+            OutputFormat::Ndjson => print_line("summary", summary),
+            OutputFormat::Json => print_document(&json!({ "items": items, "summary": summary }))
+        }
+    }
+    // 一次性输出列表与汇总
+    pub fn list<T: Serialize, S: Serialize>(&self, items: &[T], summary: &S, text: impl FnOnce(&[T], &S)) {
+        match self.format {
+            OutputFormat::Text => text(items, summary),
+            OutputFormat::Ndjson => {
+                for item in items {
+                    print_line("item", item)
+                }
+                print_line("summary", summary)
+            },
+            OutputFormat::Json => print_document(&json!({ "items": items, "summary": summary }))
+        }
+    }
+    // 输出单个结果
+    pub fn result<T: Serialize>(&self, result: &T, text: impl FnOnce(&T)) {
+        match self.format {
+            OutputFormat::Text => text(result),
+            OutputFormat::Ndjson => print_line("result", result),
+            OutputFormat::Json => print_document(result)
+        }
+    }
+    // 输出错误。text格式输出到stderr；json与ndjson格式输出错误对象到stdout，以便脚本从同一个流中读取
+    pub fn error(&self, message: &str, detail: impl Display) {
+        let detail = detail.to_string();
+        match self.format {
+            OutputFormat::Text => if detail.is_empty() { eprintln!("{}", message) }else{ eprintln!("{} {}", message, detail) },
+            OutputFormat::Ndjson => print_line("error", &json!({ "message": message, "detail": detail })),
+            OutputFormat::Json => print_document(&json!({ "error": { "message": message, "detail": detail } }))
+        }
+    }
+    // 输出带有错误链与提示的错误
+    pub fn failure(&self, message: &str, e: &HedgeError) {
+        let hints = e.hints();
+        match self.format {
+            OutputFormat::Text => {
+                eprintln!("{} {}", message, e);
+                for hint in hints {
+                    eprintln!("\x1b[1;33mhint\x1b[0m: {}", hint);
+                }
+            },
+            OutputFormat::Ndjson => print_line("error", &json!({ "message": message, "detail": e.to_string(), "hints": hints })),
+            OutputFormat::Json => print_document(&json!({ "error": { "message": message, "detail": e.to_string(), "hints": hints } }))
+        }
+    }
+}
+
+// 内容放在data中，不与内容自身的字段冲突
+fn print_line<T: Serialize>(kind: &str, value: &T) {
+    let value = match serde_json::to_value(value) {
+        Ok(data) => json!({ "type": kind, "data": data }),
+        Err(e) => json!({ "type": "error", "data": { "message": "Output serialize failed.", "detail": e.to_string() } })
+    };
+    println!("{}", value)
+}
+
+fn print_document<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(text) => println!("{}", text),
+        Err(e) => println!("{}", json!({ "error": { "message": "Output serialize failed.", "detail": e.to_string() } }))
+    }
+}